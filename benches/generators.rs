@@ -0,0 +1,50 @@
+//! Times each of the built-in generators against `Space::layout` on large
+//! square grids. Run with `cargo bench`.
+use std::time::{Duration, Instant};
+use rand::SeedableRng;
+use mazer::{
+    space::Space,
+    space_square::SpaceSquare,
+    generator::Algorithm,
+};
+
+const SIZES: [usize; 3] = [100, 300, 1000];
+/// Aldous-Broder walks until it has covered every node, which takes far
+/// longer than the others, so it only runs on the smaller grids.
+const SLOW_SIZES: [usize; 2] = [100, 300];
+const ROOTS: usize = 6;
+const RUNS: u32 = 3;
+
+fn time(mut run: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        run();
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+    for size in SIZES {
+        let space = SpaceSquare::new(size, size);
+        println!("{size}x{size}");
+        let elapsed = time(|| { space.layout(ROOTS, &mut rng).unwrap(); });
+        println!("  {:<16}{:>12.2?}", "layout", elapsed);
+        for algorithm in [
+            Algorithm::Backtracker,
+            Algorithm::Wilson,
+            Algorithm::Kruskal,
+            Algorithm::HuntAndKill,
+        ] {
+            let elapsed = time(|| { space.layout_with(ROOTS, algorithm, &mut rng).unwrap(); });
+            println!("  {:<16}{:>12.2?}", format!("{algorithm:?}"), elapsed);
+        }
+    }
+    for size in SLOW_SIZES {
+        let space = SpaceSquare::new(size, size);
+        println!("{size}x{size}");
+        let algorithm = Algorithm::AldousBroder;
+        let elapsed = time(|| { space.layout_with(ROOTS, algorithm, &mut rng).unwrap(); });
+        println!("  {:<16}{:>12.2?}", format!("{algorithm:?}"), elapsed);
+    }
+}
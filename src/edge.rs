@@ -8,6 +8,26 @@ pub enum EdgeDirection {
     Forward,
     Backward,
     Border,
+    /// Open, but not part of the tree that [`crate::space::Space::solve`]
+    /// follows back to the root, so the maze has a loop through it.
+    Open,
+}
+
+impl EdgeDirection {
+    /// Whether the maze can be walked through the edge.
+    pub fn is_open(&self) -> bool {
+        matches!(self, EdgeDirection::Forward | EdgeDirection::Backward | EdgeDirection::Open | EdgeDirection::Border)
+    }
+}
+
+/// How the two nodes of an edge are laid out relative to each other.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// The nodes are next to each other.
+    #[default]
+    Adjacent,
+    /// The edge tunnels under the node between its two nodes.
+    Under,
 }
 
 #[derive(Debug,Default)]
@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::iter::Iterator;
+use svg::node::element::{Path, Rectangle, path::Data};
+
+/// One row of a square maze from [`Eller`], as which of its walls are open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    /// Which row of the maze this is, counting from the top.
+    pub index: usize,
+    /// Whether each cell is open to the cell east of it. There is one fewer
+    /// than there are cells.
+    pub east: Vec<bool>,
+    /// Whether each cell is open to the cell below it.
+    pub south: Vec<bool>,
+}
+
+/// Eller's algorithm: lays out a square maze one row at a time, keeping
+/// only the current row in memory, so a maze can have millions of rows or
+/// go on forever.
+///
+/// Each cell carries the set of cells it is joined to through the rows
+/// above. Neighbours in different sets are joined at random, then every set
+/// carries on down through at least one cell. The last row joins whatever
+/// sets are left, so a maze with an end is a single tree, like one from
+/// [`crate::space::Space::layout`] with one root.
+pub struct Eller<R: rand::Rng> {
+    cols: usize,
+    rows: Option<usize>,
+    index: usize,
+    sets: Vec<Option<usize>>,
+    next_set: usize,
+    rng: R,
+}
+
+impl<R: rand::Rng> Eller<R> {
+    /// A maze that never ends.
+    pub fn new(cols: usize, rng: R) -> Self {
+        Self{
+            cols,
+            rows: None,
+            index: 0,
+            sets: vec![None; cols],
+            next_set: 0,
+            rng,
+        }
+    }
+
+    /// A maze with a last row.
+    pub fn with_rows(cols: usize, rows: usize, rng: R) -> Self {
+        Self{
+            rows: Some(rows),
+            ..Self::new(cols, rng)
+        }
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Puts every cell of `from` in the set `into`.
+    #[inline]
+    fn merge(&mut self, from: usize, into: usize) {
+        for set in self.sets.iter_mut() {
+            if *set == Some(from) {
+                *set = Some(into);
+            }
+        }
+    }
+}
+
+impl<R: rand::Rng> Iterator for Eller<R> {
+    type Item = Row;
+    fn next(&mut self) -> Option<Self::Item> {
+        let last = match self.rows {
+            Some(rows) if self.index >= rows => return None,
+            Some(rows) => self.index + 1 == rows,
+            None => false,
+        };
+        for set in self.sets.iter_mut().filter(|set| set.is_none()) {
+            *set = Some(self.next_set);
+            self.next_set += 1;
+        }
+
+        let mut east = vec![false; self.cols.saturating_sub(1)];
+        for (col, open) in east.iter_mut().enumerate() {
+            let (here, there) = (self.sets[col].unwrap(), self.sets[col + 1].unwrap());
+            if here != there && (last || self.rng.random_bool(0.5)) {
+                *open = true;
+                self.merge(there, here);
+            }
+        }
+
+        let mut south = vec![false; self.cols];
+        if !last {
+            let mut members = BTreeMap::<usize, Vec<usize>>::new();
+            for (col, set) in self.sets.iter().enumerate() {
+                members.entry(set.unwrap()).or_default().push(col);
+            }
+            for cols in members.values() {
+                let forced = cols[self.rng.random_range(0..cols.len())];
+                for col in cols.iter() {
+                    south[*col] = *col == forced || self.rng.random_bool(0.5);
+                }
+            }
+        }
+        for (col, set) in self.sets.iter_mut().enumerate() {
+            if !south[col] {
+                *set = None;
+            }
+        }
+
+        let row = Row{
+            index: self.index,
+            east,
+            south,
+        };
+        self.index += 1;
+        Some(row)
+    }
+}
+
+
+/// Writes rows of a square maze as ASCII art as they come, so the maze
+/// can be endless.
+pub fn write_text_rows(
+    out: &mut impl std::io::Write,
+    cols: usize,
+    rows: impl Iterator<Item=Row>,
+) -> std::io::Result<()> {
+    writeln!(out, "+{}", "--+".repeat(cols))?;
+    for row in rows {
+        let mut cells = String::from("|");
+        let mut floor = String::from("+");
+        for col in 0..cols {
+            cells.push_str(if row.east.get(col).copied().unwrap_or(false) { "   " } else { "  |" });
+            floor.push_str(if row.south[col] { "  +" } else { "--+" });
+        }
+        writeln!(out, "{cells}")?;
+        writeln!(out, "{floor}")?;
+    }
+    Ok(())
+}
+
+/// Writes `height` rows of a square maze as SVG as they come, one path of
+/// walls per row, without holding the document in memory.
+pub fn write_svg_rows(
+    out: &mut impl std::io::Write,
+    cols: usize,
+    height: usize,
+    rows: impl Iterator<Item=Row>,
+    scale: f32,
+) -> std::io::Result<()> {
+    writeln!(out,
+        "<svg viewBox=\"{} {} {} {}\" xmlns=\"http://www.w3.org/2000/svg\">",
+        -0.5 * scale, -0.5 * scale, (cols+2) as f32 * scale, (height+2) as f32 * scale,
+    )?;
+    for row in rows.take(height) {
+        let (top, bottom) = (row.index as f32 * scale, (row.index + 1) as f32 * scale);
+        let mut data = Data::new();
+        for col in 0..cols {
+            let (left, right) = (col as f32 * scale, (col + 1) as f32 * scale);
+            if col + 1 < cols && !row.east[col] {
+                data = data.move_to((right, top)).line_to((right, bottom));
+            }
+            if !row.south[col] {
+                data = data.move_to((left, bottom)).line_to((right, bottom));
+            }
+        }
+        if data.is_empty() {
+            continue
+        }
+        writeln!(out, "{}", Path::new()
+            .set("id", format!("row_{}", row.index))
+            .set("class", "wall")
+            .set("d", data)
+        )?;
+    }
+    writeln!(out, "{}", Rectangle::new()
+        .set("class", "wall")
+        .set("x", 0)
+        .set("y", 0)
+        .set("width", cols as f32 * scale)
+        .set("height", height as f32 * scale)
+    )?;
+    writeln!(out, "</svg>")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_rows() {
+        init_tests();
+        let rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let (cols, rows) = (9, 7);
+        let maze = Eller::with_rows(cols, rows, rng).collect::<Vec<_>>();
+        assert_eq!(maze.len(), rows);
+        assert!(maze.last().unwrap().south.iter().all(|open| !open));
+
+        // Joined through every open wall, the cells are one tree.
+        let mut reached = vec![false; cols * rows];
+        let mut stack = vec![0];
+        reached[0] = true;
+        let mut open = 0;
+        while let Some(nid) = stack.pop() {
+            let (row, col) = (nid / cols, nid % cols);
+            let mut neighbours = Vec::new();
+            if col + 1 < cols && maze[row].east[col] { neighbours.push(nid + 1) }
+            if col > 0 && maze[row].east[col - 1] { neighbours.push(nid - 1) }
+            if maze[row].south[col] { neighbours.push(nid + cols) }
+            if row > 0 && maze[row - 1].south[col] { neighbours.push(nid - cols) }
+            for other in neighbours {
+                if !reached[other] {
+                    reached[other] = true;
+                    stack.push(other);
+                }
+            }
+        }
+        assert!(reached.iter().all(|r| *r));
+        for row in maze.iter() {
+            open += row.east.iter().filter(|o| **o).count() + row.south.iter().filter(|o| **o).count();
+        }
+        assert_eq!(open, cols * rows - 1);
+    }
+
+    #[test]
+    fn test_endless() {
+        init_tests();
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(2);
+        let mut maze = Eller::new(5, &mut rng);
+        let row = maze.nth(10_000).unwrap();
+        assert_eq!(row.index, 10_000);
+        assert!(row.south.iter().any(|open| *open));
+        assert_eq!(maze.sets.len(), 5);
+    }
+
+    #[test]
+    fn test_render() {
+        init_tests();
+        let rng = rand_chacha::ChaCha12Rng::seed_from_u64(3);
+        let mut text = Vec::<u8>::new();
+        write_text_rows(&mut text, 4, Eller::with_rows(4, 3, rng.clone())).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], "+--+--+--+--+");
+        assert_eq!(lines[6], "+--+--+--+--+");
+        assert!(lines.iter().skip(1).step_by(2).all(|line| line.starts_with('|') && line.ends_with('|')));
+
+        let mut svg = Vec::<u8>::new();
+        write_svg_rows(&mut svg, 4, 3, Eller::with_rows(4, 3, rng), 10.0).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("class=\"wall\"").count(), 4);
+    }
+}
@@ -0,0 +1,1034 @@
+use crate::{
+    node::Node,
+    edge::{Edge,EdgeDirection},
+    space::*,
+    space_square::SpaceSquare,
+    error::*,
+};
+
+/// A way of carving a maze out of a [`Space`].
+///
+/// A generator starts from every edge [`EdgeDirection::Unknown`] and must
+/// leave each edge between enabled nodes either closed or open, with the
+/// open edges forming one tree per root, set with [`open_edge`] so that
+/// [`Space::solve`] can follow them back to the root. Every node reached
+/// takes the group of its root.
+pub trait Generator {
+    fn generate(&self, space: &impl Space, roots: usize, rng: &mut impl rand::Rng) -> Result<(Vec<Node>, Vec<Edge>)>;
+}
+
+impl<G: Generator> Generator for &G {
+    fn generate(&self, space: &impl Space, roots: usize, rng: &mut impl rand::Rng) -> Result<(Vec<Node>, Vec<Edge>)> {
+        (*self).generate(space, roots, rng)
+    }
+}
+
+/// A node and an edge for everything in the space, none of them visited.
+pub fn blank(space: &impl Space) -> (Vec<Node>, Vec<Edge>) {
+    let num_nodes = space.num_nodes();
+    let num_edges = space.num_edges();
+    let mut nodes = Vec::<Node>::with_capacity(num_nodes);
+    for _ in 0..num_nodes {
+        nodes.push(Default::default());
+    }
+    let mut edges = Vec::<Edge>::with_capacity(num_edges);
+    for _ in 0..num_edges {
+        edges.push(Default::default())
+    }
+    (nodes, edges)
+}
+
+/// Picks `roots` random enabled nodes as roots, each in a group of its
+/// own, numbered from zero.
+pub fn plant_roots(space: &impl Space, roots: usize, nodes: &mut [Node], rng: &mut impl rand::Rng) -> Result<Vec<usize>> {
+    let num_nodes = space.num_nodes();
+    if roots > 0 && (0..num_nodes).filter(|nid| space.node_enabled(*nid)).nth(roots - 1).is_none() {
+        return Err(Error::MessageError("more roots than enabled nodes"))
+    }
+    let mut planted = Vec::<usize>::with_capacity(roots);
+    for (i, idx) in n_unique_indices(space, rng, num_nodes, roots)?.enumerate() {
+        nodes[idx].group = Some(i);
+        nodes[idx].root = true;
+        planted.push(idx);
+    }
+    Ok(planted)
+}
+
+#[inline]
+fn backtrack(space: &impl Space, rng: &mut impl rand::Rng, nodes: &mut [Node], edges: &mut [Edge], stacks: &mut Vec<Vec<usize>>) -> Result<()> {
+    let sidx = rng.random_range(0..stacks.len());
+    let stack = &mut stacks[sidx];
+    let idx = *stack.last().unwrap();
+    let node_group = nodes[idx].group;
+    let mut unvisited = Vec::<(usize, usize)>::new();
+    node_nodes(space, idx, edges, |edge, nidx| {
+        if nodes[nidx].group.is_some() {
+            edge.direction = EdgeDirection::Closed;
+        }
+    });
+    space.node_edges(idx).for_each(|eid| if edges[eid].direction == EdgeDirection::Unknown {
+        unvisited.push((eid, follow_edge(space, idx, eid).unwrap()));
+    });
+    if unvisited.is_empty() {
+        stack.pop();
+        if stack.is_empty() {
+            stacks.swap_remove(sidx);
+        }
+        return Ok(())
+    }
+    let (eid, nidx) = unvisited[rng.random_range(0..unvisited.len())];
+    open_edge(&mut edges[eid], idx, nidx);
+    nodes[nidx].group = node_group;
+    stack.push(nidx);
+    Ok(())
+}
+
+/// Closes every edge of a reached node that was never opened.
+fn close_unknown(space: &impl Space, nodes: &[Node], edges: &mut [Edge]) {
+    for (idx, node) in nodes.iter().enumerate() {
+        if node.group.is_some() {
+            node_nodes(space, idx, edges, |edge, _nidx| edge.direction = EdgeDirection::Closed);
+        }
+    }
+}
+
+/// Every node that can be reached from the roots.
+fn reachable(space: &impl Space, planted: &[usize]) -> Vec<bool> {
+    let mut seen = vec![false; space.num_nodes()];
+    let mut queue = planted.to_vec();
+    for idx in planted {
+        seen[*idx] = true;
+    }
+    while let Some(idx) = queue.pop() {
+        for eid in space.node_edges(idx) {
+            let nidx = follow_edge(space, idx, eid).unwrap();
+            if !seen[nidx] {
+                seen[nidx] = true;
+                queue.push(nidx);
+            }
+        }
+    }
+    seen
+}
+
+/// Random walks from each node left out until they reach the tree, erasing
+/// any loops, then adds the walk to the group it reached.
+fn wilson(space: &impl Space, rng: &mut impl rand::Rng, nodes: &mut [Node], edges: &mut [Edge], planted: &[usize]) {
+    let reach = reachable(space, planted);
+    // The way each node was last left. Walking over a loop overwrites it,
+    // which erases the loop.
+    let mut exits = vec![(0usize, 0usize); space.num_nodes()];
+    for start in 0..space.num_nodes() {
+        if !reach[start] || nodes[start].group.is_some() {
+            continue
+        }
+        let mut idx = start;
+        while nodes[idx].group.is_none() {
+            let eid = space.node_edges(idx)
+                .nth(rng.random_range(0..space.node_edges(idx).count()))
+                .unwrap();
+            let nidx = follow_edge(space, idx, eid).unwrap();
+            exits[idx] = (eid, nidx);
+            idx = nidx;
+        }
+        let group = nodes[idx].group;
+        let mut idx = start;
+        while nodes[idx].group.is_none() {
+            let (eid, nidx) = exits[idx];
+            open_edge(&mut edges[eid], nidx, idx);
+            nodes[idx].group = group;
+            idx = nidx;
+        }
+    }
+    close_unknown(space, nodes, edges);
+}
+
+/// Gives the chosen edges directions, and the nodes their groups, by
+/// flooding out from the roots through them. A chosen edge found joining
+/// two nodes already reached closes a loop, and is left
+/// [`EdgeDirection::Open`], unless it is where two zones meet. Every other
+/// edge of a reached node is closed.
+fn orient(space: &impl Space, nodes: &mut [Node], edges: &mut [Edge], chosen: &[bool], planted: Vec<usize>) {
+    let mut visiting = std::collections::VecDeque::from(planted);
+    while let Some(idx) = visiting.pop_front() {
+        let group = nodes[idx].group;
+        for eid in space.node_edges(idx) {
+            if edges[eid].direction != EdgeDirection::Unknown || !chosen[eid] {
+                continue
+            }
+            let nidx = follow_edge(space, idx, eid).unwrap();
+            match nodes[nidx].group {
+                None => {
+                    open_edge(&mut edges[eid], idx, nidx);
+                    nodes[nidx].group = group;
+                    visiting.push_back(nidx);
+                }
+                Some(other) if Some(other) == group => edges[eid].direction = EdgeDirection::Open,
+                Some(_) => edges[eid].direction = EdgeDirection::Closed,
+            }
+        }
+    }
+    close_unknown(space, nodes, edges);
+}
+
+/// Carves a random walk from each root until it runs into itself, then
+/// hunts from the first node for one not yet reached next to one that
+/// has been, joins them, and walks on from there.
+fn hunt_and_kill(space: &impl Space, rng: &mut impl rand::Rng, nodes: &mut [Node], edges: &mut [Edge], planted: Vec<usize>) {
+    let mut walkers = planted;
+    // Every node before this one has been reached.
+    let mut hunt_from = 0;
+    let mut unvisited = Vec::<(usize, usize)>::new();
+    while !walkers.is_empty() {
+        let widx = rng.random_range(0..walkers.len());
+        let idx = walkers[widx];
+        unvisited.clear();
+        unvisited.extend(space.node_edges(idx)
+            .map(|eid| (eid, follow_edge(space, idx, eid).unwrap()))
+            .filter(|(_eid, nidx)| nodes[*nidx].group.is_none()));
+        if !unvisited.is_empty() {
+            let (eid, nidx) = unvisited[rng.random_range(0..unvisited.len())];
+            open_edge(&mut edges[eid], idx, nidx);
+            nodes[nidx].group = nodes[idx].group;
+            walkers[widx] = nidx;
+            continue
+        }
+        while hunt_from < nodes.len() && nodes[hunt_from].group.is_some() {
+            hunt_from += 1;
+        }
+        let found = (hunt_from..nodes.len())
+            .filter(|nidx| nodes[*nidx].group.is_none())
+            .find(|nidx| {
+                unvisited.clear();
+                unvisited.extend(space.node_edges(*nidx)
+                    .map(|eid| (eid, follow_edge(space, *nidx, eid).unwrap()))
+                    .filter(|(_eid, other)| nodes[*other].group.is_some()));
+                !unvisited.is_empty()
+            });
+        match found {
+            None => {
+                walkers.swap_remove(widx);
+            }
+            Some(nidx) => {
+                let (eid, parent) = unvisited[rng.random_range(0..unvisited.len())];
+                open_edge(&mut edges[eid], parent, nidx);
+                nodes[nidx].group = nodes[parent].group;
+                walkers[widx] = nidx;
+            }
+        }
+    }
+    close_unknown(space, nodes, edges);
+}
+
+/// Random walks from each root, through nodes already reached or not,
+/// opening the edge into each node the first time it is reached. Every
+/// spanning tree is equally likely, but the walks take a long time to
+/// find the last few nodes.
+fn aldous_broder(space: &impl Space, rng: &mut impl rand::Rng, nodes: &mut [Node], edges: &mut [Edge], planted: Vec<usize>) {
+    let mut left = reachable(space, &planted).iter().filter(|r| **r).count() - planted.len();
+    let mut walkers = planted;
+    // A root cut off from everything by a mask has nowhere to walk.
+    walkers.retain(|idx| space.node_edges(*idx).next().is_some());
+    while left > 0 {
+        let widx = rng.random_range(0..walkers.len());
+        let idx = walkers[widx];
+        let eid = space.node_edges(idx)
+            .nth(rng.random_range(0..space.node_edges(idx).count()))
+            .unwrap();
+        let nidx = follow_edge(space, idx, eid).unwrap();
+        if nodes[nidx].group.is_none() {
+            open_edge(&mut edges[eid], idx, nidx);
+            nodes[nidx].group = nodes[idx].group;
+            left -= 1;
+        }
+        walkers[widx] = nidx;
+    }
+    close_unknown(space, nodes, edges);
+}
+
+/// Picks an index in proportion to its weight. Weights of zero or less
+/// are only picked when every weight is.
+fn weighted_index(rng: &mut impl rand::Rng, weights: &[f64]) -> usize {
+    let positive = |w: f64| if w > 0.0 { w } else { 0.0 };
+    let total = weights.iter().map(|w| positive(*w)).sum::<f64>();
+    if total <= 0.0 {
+        return rng.random_range(0..weights.len())
+    }
+    let mut choice = rng.random_range(0.0..total);
+    for (k, w) in weights.iter().enumerate() {
+        choice -= positive(*w);
+        if choice < 0.0 && *w > 0.0 {
+            return k
+        }
+    }
+    weights.iter().rposition(|w| *w > 0.0).unwrap()
+}
+
+/// One step of the frontier flood of [`visit`], but opening the way to
+/// each unvisited neighbour in proportion to the weight of the edge to it,
+/// against the heaviest such edge. A node with neighbours left unopened
+/// goes back on `visiting` to try again later, so the maze spreads fastest
+/// along heavy edges, and equal weights flood exactly as [`visit`] does.
+fn frontier(space: &impl Space, rng: &mut impl rand::Rng, nodes: &mut [Node], edges: &mut [Edge], visiting: &mut Vec<usize>, weight: &impl Fn(usize) -> f64) {
+    let idx = visiting.swap_remove(rng.random_range(0..visiting.len()));
+    let choices = space.node_edges(idx)
+        .filter(|eid| edges[*eid].direction == EdgeDirection::Unknown)
+        .map(|eid| (eid, follow_edge(space, idx, eid).unwrap()))
+        .collect::<Vec<_>>();
+    let heaviest = choices.iter()
+        .filter(|(_eid, nidx)| nodes[*nidx].group.is_none())
+        .map(|(eid, _nidx)| weight(*eid))
+        .fold(0.0, f64::max);
+    let mut skipped = false;
+    for (eid, nidx) in choices {
+        if nodes[nidx].group.is_some() {
+            edges[eid].direction = EdgeDirection::Closed;
+            continue
+        }
+        let w = weight(eid);
+        if heaviest > 0.0 && w < heaviest && !rng.random_bool(w.max(0.0) / heaviest) {
+            skipped = true;
+            continue
+        }
+        open_edge(&mut edges[eid], idx, nidx);
+        nodes[nidx].group = nodes[idx].group;
+        visiting.push(nidx);
+    }
+    if skipped {
+        visiting.push(idx);
+    }
+}
+
+/// A frontier generator like [`Algorithm::Frontier`], with a weight
+/// function over edge ids to bias which way it spreads, for example
+/// favouring east-west edges to make a maze of long horizontal runs.
+/// Heavy edges are opened sooner than light ones, and equal weights give
+/// the same maze as [`Algorithm::Frontier`].
+#[derive(Debug, Clone)]
+pub struct Frontier<W = fn(usize) -> f64> {
+    weight: Option<W>,
+}
+
+impl Frontier {
+    pub fn new() -> Self {
+        Self{
+            weight: None,
+        }
+    }
+}
+
+impl Default for Frontier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Fn(usize) -> f64> Frontier<W> {
+    /// Opens the edge to each unvisited neighbour in proportion to its
+    /// weight. Edges weighted zero are only opened when there is no other
+    /// way to go.
+    pub fn with_weight<V: Fn(usize) -> f64>(self, weight: V) -> Frontier<V> {
+        Frontier{
+            weight: Some(weight),
+        }
+    }
+}
+
+impl<W: Fn(usize) -> f64> Generator for Frontier<W> {
+    fn generate(&self, space: &impl Space, roots: usize, rng: &mut impl rand::Rng) -> Result<(Vec<Node>, Vec<Edge>)> {
+        let Some(weight) = &self.weight else {
+            return Algorithm::Frontier.generate(space, roots, rng)
+        };
+        let (mut nodes, mut edges) = blank(space);
+        let mut visiting = plant_roots(space, roots, &mut nodes, rng)?;
+        while !visiting.is_empty() {
+            frontier(space, rng, &mut nodes, &mut edges, &mut visiting, weight);
+        }
+        Ok((nodes, edges))
+    }
+}
+
+/// Disjoint sets of nodes, for finding whether two nodes are already
+/// joined.
+struct UnionFind {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self{
+            parents: (0..len).collect(),
+            sizes: vec![1; len],
+        }
+    }
+
+    fn find(&mut self, idx: usize) -> usize {
+        let mut root = idx;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        let mut idx = idx;
+        while self.parents[idx] != root {
+            let next = self.parents[idx];
+            self.parents[idx] = root;
+            idx = next;
+        }
+        root
+    }
+
+    /// Joins the sets of two nodes, if they are not already joined.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false
+        }
+        let (big, small) = if self.sizes[a] < self.sizes[b] { (b, a) } else { (a, b) };
+        self.parents[small] = big;
+        self.sizes[big] += self.sizes[small];
+        true
+    }
+}
+
+/// Randomized Kruskal: takes every edge in a random order and opens it if
+/// it joins two parts of the maze that are not yet joined.
+///
+/// Rooms are sets of nodes joined before anything else, with every edge
+/// between them open, so they are stamped into the maze as open areas.
+/// The edges of a room that are not needed to reach each of its nodes are
+/// left [`EdgeDirection::Open`].
+#[derive(Debug, Default, Clone)]
+pub struct Kruskal {
+    rooms: Vec<Vec<usize>>,
+}
+
+impl Kruskal {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_room(mut self, nodes: Vec<usize>) -> Self {
+        self.rooms.push(nodes);
+        self
+    }
+}
+
+impl Generator for Kruskal {
+    fn generate(&self, space: &impl Space, roots: usize, rng: &mut impl rand::Rng) -> Result<(Vec<Node>, Vec<Edge>)> {
+        let (mut nodes, mut edges) = blank(space);
+        let num_nodes = space.num_nodes();
+        for room in self.rooms.iter() {
+            if let Some(nid) = room.iter().find(|nid| **nid >= num_nodes) {
+                return Err(Error::NodeOutOfBounds(*nid, num_nodes))
+            }
+        }
+        let planted = plant_roots(space, roots, &mut nodes, rng)?;
+        kruskal(space, rng, &mut nodes, &mut edges, planted, &self.rooms);
+        Ok((nodes, edges))
+    }
+}
+
+/// Joins each room, then opens edges in a random order wherever they join
+/// two parts of the maze that are not yet joined.
+fn kruskal(space: &impl Space, rng: &mut impl rand::Rng, nodes: &mut [Node], edges: &mut [Edge], planted: Vec<usize>, rooms: &[Vec<usize>]) {
+    use rand::seq::SliceRandom;
+    let num_nodes = space.num_nodes();
+    let mut sets = UnionFind::new(num_nodes);
+    let mut chosen = vec![false; edges.len()];
+    let mut in_room = vec![false; num_nodes];
+    for room in rooms.iter() {
+        room.iter().for_each(|nid| in_room[*nid] = true);
+        for nid in room.iter() {
+            for eid in space.node_edges(*nid) {
+                let other = follow_edge(space, *nid, eid).unwrap();
+                if in_room[other] {
+                    sets.union(*nid, other);
+                    chosen[eid] = true;
+                }
+            }
+        }
+        room.iter().for_each(|nid| in_room[*nid] = false);
+    }
+    let mut order = (0..num_nodes)
+        .flat_map(|nid| space.node_edges(nid).filter(move |eid| follow_edge(space, nid, *eid).unwrap() > nid))
+        .collect::<Vec<_>>();
+    order.shuffle(rng);
+    for eid in order {
+        let mut ends = space.edge_nodes(eid);
+        let (a, b) = (ends.next().unwrap(), ends.next().unwrap());
+        if sets.union(a, b) {
+            chosen[eid] = true;
+        }
+    }
+
+    orient(space, nodes, edges, &chosen, planted);
+}
+
+/// Recursive division: starts from one open room the size of a
+/// [`SpaceSquare`] and splits it in two with a wall that has a single gap,
+/// then splits each half the same way, which gives long straight walls.
+///
+/// Rooms no more than `min_room` cells across in either direction are left
+/// open, with [`EdgeDirection::Open`] edges. With a `min_room` of `1` every
+/// room is split down to single cells, so there are no loops.
+///
+/// Only edges between cells next to each other in the grid are ever open,
+/// so the edges round a wrapped space are closed. Masked and woven spaces
+/// are refused, as a dividing wall could cut off cells the mask leaves on
+/// one side of it.
+#[derive(Debug, Clone)]
+pub struct RecursiveDivision {
+    rows: usize,
+    cols: usize,
+    min_room: usize,
+}
+
+impl RecursiveDivision {
+    pub fn new(space: &SpaceSquare, min_room: usize) -> Self {
+        Self{
+            rows: space.rows(),
+            cols: space.cols(),
+            min_room: min_room.max(1),
+        }
+    }
+}
+
+impl Generator for RecursiveDivision {
+    fn generate(&self, space: &impl Space, roots: usize, rng: &mut impl rand::Rng) -> Result<(Vec<Node>, Vec<Edge>)> {
+        if space.num_nodes() != self.rows * self.cols {
+            return Err(Error::MessageError("recursive division needs the square space it was made for"))
+        }
+        let (rows, cols) = (self.rows, self.cols);
+        // Every cell must be enabled, and every edge must join cells next
+        // to each other, or round a wrapped side, not tunnel under one.
+        let step = |a: usize, b: usize, len: usize| a.abs_diff(b) == 1 || a.abs_diff(b) + 1 == len;
+        let beside = |eid: usize| {
+            let mut ends = space.edge_nodes(eid).map(|nid| (nid / cols, nid % cols));
+            let ((arow, acol), (brow, bcol)) = (ends.next().unwrap(), ends.next().unwrap());
+            (arow == brow && step(acol, bcol, cols)) || (acol == bcol && step(arow, brow, rows))
+        };
+        if !(0..space.num_nodes()).all(|nid| space.node_enabled(nid)) || !(0..space.num_edges()).all(beside) {
+            return Err(Error::MessageError("recursive division cannot divide a masked or woven space"))
+        }
+        let (mut nodes, mut edges) = blank(space);
+        let planted = plant_roots(space, roots, &mut nodes, rng)?;
+        let edge_between = |a: usize, b: usize| space.node_edges(a)
+            .find(|eid| follow_edge(space, a, *eid) == Some(b));
+
+        let mut walled = vec![false; edges.len()];
+        // Rooms as top, left, height and width.
+        let mut rooms = vec![(0, 0, self.rows, self.cols)];
+        while let Some((top, left, height, width)) = rooms.pop() {
+            let across = match (height > self.min_room, width > self.min_room) {
+                (false, false) => continue,
+                (true, false) => true,
+                (false, true) => false,
+                (true, true) if height == width => rng.random_bool(0.5),
+                (true, true) => height > width,
+            };
+            if across {
+                let cut = rng.random_range(1..height);
+                let gap = rng.random_range(0..width);
+                for col in (left..left + width).filter(|col| *col != left + gap) {
+                    let above = (top + cut - 1) * cols + col;
+                    if let Some(eid) = edge_between(above, above + cols) {
+                        walled[eid] = true;
+                    }
+                }
+                rooms.push((top, left, cut, width));
+                rooms.push((top + cut, left, height - cut, width));
+            } else {
+                let cut = rng.random_range(1..width);
+                let gap = rng.random_range(0..height);
+                for row in (top..top + height).filter(|row| *row != top + gap) {
+                    let before = row * cols + left + cut - 1;
+                    if let Some(eid) = edge_between(before, before + 1) {
+                        walled[eid] = true;
+                    }
+                }
+                rooms.push((top, left, height, cut));
+                rooms.push((top, left + cut, height, width - cut));
+            }
+        }
+
+        let mut chosen = vec![false; edges.len()];
+        for nid in 0..space.num_nodes() {
+            let (row, col) = (nid / cols, nid % cols);
+            for eid in space.node_edges(nid) {
+                let other = follow_edge(space, nid, eid).unwrap();
+                let (orow, ocol) = (other / cols, other % cols);
+                chosen[eid] = !walled[eid] && row.abs_diff(orow) + col.abs_diff(ocol) == 1;
+            }
+        }
+        orient(space, &mut nodes, &mut edges, &chosen, planted);
+        Ok((nodes, edges))
+    }
+}
+
+/// Which of the active nodes [`GrowingTree`] carves from next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// The node added last, which makes a recursive backtracker.
+    Newest,
+    /// The node added first, which makes long straight corridors fanning
+    /// out from the roots.
+    Oldest,
+    /// Any active node, which makes short branches and many dead ends.
+    Random,
+}
+
+/// The growing tree: keeps a list of active nodes, carves from one of them
+/// to an unvisited neighbour, which becomes active too, and drops nodes
+/// with nowhere left to go. Which node to carve from is chosen by a
+/// weighted mix of [`Selection`]s, which tunes the maze between long
+/// corridors and many short dead ends.
+///
+/// A weight function over edge ids biases which unvisited neighbour is
+/// carved to, for example favouring east-west passages to make a maze of
+/// long horizontal runs.
+#[derive(Debug, Clone)]
+pub struct GrowingTree<W = fn(usize) -> f64> {
+    mix: Vec<(Selection, f64)>,
+    weight: Option<W>,
+}
+
+impl GrowingTree {
+    pub fn new(selection: Selection) -> Self {
+        Self{
+            mix: vec![(selection, 1.0)],
+            weight: None,
+        }
+    }
+
+    /// Chooses each selection in proportion to its weight, so
+    /// `[(Newest, 0.75), (Random, 0.25)]` carves from the newest node three
+    /// times out of four.
+    pub fn mix(mix: Vec<(Selection, f64)>) -> Result<Self> {
+        if mix.iter().any(|(_selection, weight)| weight.is_nan() || *weight < 0.0) {
+            return Err(Error::MessageError("selection weights cannot be negative"))
+        }
+        if mix.iter().map(|(_selection, weight)| weight).sum::<f64>() <= 0.0 {
+            return Err(Error::MessageError("at least one selection must have a weight"))
+        }
+        Ok(Self{
+            mix,
+            weight: None,
+        })
+    }
+}
+
+impl<W: Fn(usize) -> f64> GrowingTree<W> {
+    /// Carves to each unvisited neighbour in proportion to the weight of
+    /// the edge to it. Edges weighted zero are only taken when there is no
+    /// other way to go.
+    pub fn with_weight<V: Fn(usize) -> f64>(self, weight: V) -> GrowingTree<V> {
+        GrowingTree{
+            mix: self.mix,
+            weight: Some(weight),
+        }
+    }
+
+    fn carve(&self, rng: &mut impl rand::Rng, choices: &[(usize, usize)]) -> (usize, usize) {
+        let Some(weight) = &self.weight else {
+            return choices[rng.random_range(0..choices.len())]
+        };
+        let weights = choices.iter()
+            .map(|(eid, _nidx)| weight(*eid))
+            .collect::<Vec<_>>();
+        choices[weighted_index(rng, &weights)]
+    }
+
+    fn select(&self, rng: &mut impl rand::Rng, len: usize) -> usize {
+        let selection = match self.mix.as_slice() {
+            [(selection, _weight)] => *selection,
+            mix => {
+                let total = mix.iter().map(|(_selection, weight)| weight).sum::<f64>();
+                let mut choice = rng.random_range(0.0..total);
+                mix.iter()
+                    .find(|(_selection, weight)| {
+                        choice -= weight;
+                        choice < 0.0
+                    })
+                    .unwrap_or(mix.last().unwrap())
+                    .0
+            }
+        };
+        match selection {
+            Selection::Newest => len - 1,
+            Selection::Oldest => 0,
+            Selection::Random => rng.random_range(0..len),
+        }
+    }
+}
+
+impl<W: Fn(usize) -> f64> Generator for GrowingTree<W> {
+    fn generate(&self, space: &impl Space, roots: usize, rng: &mut impl rand::Rng) -> Result<(Vec<Node>, Vec<Edge>)> {
+        let (mut nodes, mut edges) = blank(space);
+        let mut active = std::collections::VecDeque::from(plant_roots(space, roots, &mut nodes, rng)?);
+        let mut unvisited = Vec::<(usize, usize)>::new();
+        // Newest and oldest need the active nodes in the order they were
+        // added. If only random picks are made, a dead end can be swapped
+        // out instead of shifting the rest along.
+        let ordered = self.mix.iter().any(|(selection, weight)| *selection != Selection::Random && *weight > 0.0);
+        while !active.is_empty() {
+            let aidx = self.select(rng, active.len());
+            let idx = active[aidx];
+            unvisited.clear();
+            node_nodes(space, idx, &mut edges, |edge, nidx| {
+                if nodes[nidx].group.is_some() {
+                    edge.direction = EdgeDirection::Closed;
+                }
+            });
+            space.node_edges(idx).for_each(|eid| if edges[eid].direction == EdgeDirection::Unknown {
+                unvisited.push((eid, follow_edge(space, idx, eid).unwrap()));
+            });
+            if unvisited.is_empty() {
+                if ordered {
+                    active.remove(aidx);
+                } else {
+                    active.swap_remove_back(aidx);
+                }
+                continue
+            }
+            let (eid, nidx) = self.carve(rng, &unvisited);
+            open_edge(&mut edges[eid], idx, nidx);
+            nodes[nidx].group = nodes[idx].group;
+            active.push_back(nidx);
+        }
+        Ok((nodes, edges))
+    }
+}
+
+/// The generators built into the crate.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Grows each zone from a random node on its frontier, which gives
+    /// short, bushy branches. [`Frontier`] can bias which way it grows.
+    #[default]
+    Frontier,
+    /// Carves a random walk from the newest node, backing up only at dead
+    /// ends: the recursive backtracker. Gives long, winding corridors.
+    Backtracker,
+    /// Joins each node to the roots by a loop-erased random walk: Wilson's
+    /// algorithm. Every spanning tree is equally likely, so there is no bias
+    /// around the roots.
+    Wilson,
+    /// [`Kruskal`], without rooms.
+    Kruskal,
+    /// Carves a random walk until it runs into itself, then hunts for a
+    /// node to carry on from. Gives long corridors like the backtracker,
+    /// without keeping a stack.
+    HuntAndKill,
+    /// Random walks that open the way into each node the first time they
+    /// reach it. Uniform like [`Algorithm::Wilson`], but slower: the walks
+    /// only stop once they have covered every node, which takes time
+    /// growing faster than the number of nodes. A 300x300 grid takes about
+    /// a second and a 1000x1000 grid about half a minute, against a second
+    /// or so for the others.
+    AldousBroder,
+}
+
+impl Generator for Algorithm {
+    fn generate(&self, space: &impl Space, roots: usize, rng: &mut impl rand::Rng) -> Result<(Vec<Node>, Vec<Edge>)> {
+        let (mut nodes, mut edges) = blank(space);
+        let planted = plant_roots(space, roots, &mut nodes, rng)?;
+        match self {
+            Algorithm::Frontier => {
+                let mut visiting = planted;
+                while !visiting.is_empty() {
+                    visit(space, rng, &mut nodes, &mut edges, &mut visiting)?;
+                }
+            }
+            Algorithm::Backtracker => {
+                let mut stacks = planted.into_iter().map(|idx| vec![idx]).collect::<Vec<_>>();
+                while !stacks.is_empty() {
+                    backtrack(space, rng, &mut nodes, &mut edges, &mut stacks)?;
+                }
+            }
+            Algorithm::Wilson => wilson(space, rng, &mut nodes, &mut edges, &planted),
+            Algorithm::Kruskal => kruskal(space, rng, &mut nodes, &mut edges, planted, &[]),
+            Algorithm::HuntAndKill => hunt_and_kill(space, rng, &mut nodes, &mut edges, planted),
+            Algorithm::AldousBroder => aldous_broder(space, rng, &mut nodes, &mut edges, planted),
+        }
+        Ok((nodes, edges))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::space_square::SpaceSquare;
+    use super::*;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    /// Always takes the oldest node off the frontier: a breadth-first flood.
+    struct Breadth;
+
+    impl Generator for Breadth {
+        fn generate(&self, space: &impl Space, roots: usize, rng: &mut impl rand::Rng) -> Result<(Vec<Node>, Vec<Edge>)> {
+            let (mut nodes, mut edges) = blank(space);
+            let mut visiting = std::collections::VecDeque::from(plant_roots(space, roots, &mut nodes, rng)?);
+            while let Some(idx) = visiting.pop_front() {
+                let group = nodes[idx].group;
+                node_nodes(space, idx, &mut edges, |edge, nidx| {
+                    if nodes[nidx].group.is_some() {
+                        edge.direction = EdgeDirection::Closed;
+                        return
+                    }
+                    open_edge(edge, idx, nidx);
+                    nodes[nidx].group = group;
+                    visiting.push_back(nidx);
+                });
+            }
+            Ok((nodes, edges))
+        }
+    }
+
+    #[test]
+    fn test_custom_generator() {
+        init_tests();
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(5, 5);
+        assert!(space.layout_with(26, Breadth, &mut rng).is_err());
+        let (nodes, mut edges) = space.layout_with(1, &Breadth, &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group == Some(0)));
+        let root = nodes.iter().position(|n| n.root).unwrap();
+        let far = if root == 0 { 24 } else { 0 };
+        // Breadth first, the path from the root is as short as it can be.
+        let (dy, dx) = ((root / 5).abs_diff(far / 5), (root % 5).abs_diff(far % 5));
+        assert_eq!(space.solve(&mut edges, root, far).count(), dy + dx);
+    }
+
+    #[test]
+    fn test_wilson() {
+        init_tests();
+        use rand::SeedableRng;
+        use crate::mask::Mask;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(12, 12);
+        let (nodes, mut edges) = space.layout_with(2, Algorithm::Wilson, &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group.is_some()));
+        assert!(edges.iter().all(|e| e.direction != EdgeDirection::Unknown));
+        let open = edges.iter().filter(|e| e.direction != EdgeDirection::Closed).count();
+        assert_eq!(open, space.num_nodes() - 2);
+        let root = nodes.iter().position(|n| n.root).unwrap();
+        let other = (0..space.num_nodes()).rev().find(|nid| *nid != root && nodes[*nid].group == nodes[root].group).unwrap();
+        assert!(space.solve(&mut edges, root, other).count() > 0);
+
+        // The island can never be reached, but must not stop the walks.
+        let space = SpaceSquare::with_mask(Mask::from_ascii("##.#\n##.#"), Default::default());
+        let (nodes, _edges) = space.layout_with(1, Algorithm::Wilson, &mut rng).unwrap();
+        assert_eq!(nodes.iter().filter(|n| n.group.is_some()).count(), if nodes[3].root || nodes[7].root { 2 } else { 4 });
+
+        // A two by two grid has four spanning trees, each missing one edge.
+        let space = SpaceSquare::new(2, 2);
+        let mut counts = [0usize; 4];
+        for _ in 0..4000 {
+            let (_nodes, edges) = space.layout_with(1, Algorithm::Wilson, &mut rng).unwrap();
+            let closed = edges.iter().position(|e| e.direction == EdgeDirection::Closed).unwrap();
+            counts[closed] += 1;
+        }
+        assert!(counts.iter().all(|c| (850..1150).contains(c)), "{counts:?}");
+    }
+
+    #[test]
+    fn test_hunt_and_kill() {
+        init_tests();
+        use rand::SeedableRng;
+        use crate::mask::Mask;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(15, 15);
+        let (nodes, edges) = space.layout_with(3, Algorithm::HuntAndKill, &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group.is_some()));
+        assert!(edges.iter().all(|e| e.direction != EdgeDirection::Unknown));
+        let open = edges.iter().filter(|e| e.direction.is_open()).count();
+        assert_eq!(open, space.num_nodes() - 3);
+
+        let space = SpaceSquare::with_mask(Mask::from_ascii("##.#\n##.#"), Default::default());
+        for algorithm in [Algorithm::HuntAndKill, Algorithm::AldousBroder] {
+            let (nodes, _edges) = space.layout_with(1, algorithm, &mut rng).unwrap();
+            assert_eq!(nodes.iter().filter(|n| n.group.is_some()).count(), if nodes[3].root || nodes[7].root { 2 } else { 4 });
+        }
+    }
+
+    #[test]
+    fn test_aldous_broder() {
+        init_tests();
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(10, 10);
+        let (nodes, edges) = space.layout_with(2, Algorithm::AldousBroder, &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group.is_some()));
+        assert!(edges.iter().all(|e| e.direction != EdgeDirection::Unknown));
+        let open = edges.iter().filter(|e| e.direction.is_open()).count();
+        assert_eq!(open, space.num_nodes() - 2);
+
+        let space = SpaceSquare::new(2, 2);
+        let mut counts = [0usize; 4];
+        for _ in 0..4000 {
+            let (_nodes, edges) = space.layout_with(1, Algorithm::AldousBroder, &mut rng).unwrap();
+            let closed = edges.iter().position(|e| e.direction == EdgeDirection::Closed).unwrap();
+            counts[closed] += 1;
+        }
+        assert!(counts.iter().all(|c| (850..1150).contains(c)), "{counts:?}");
+
+        // The first cell is cut off by the mask, so a root there has no
+        // edges to walk.
+        let space = SpaceSquare::with_mask(crate::mask::Mask::from_ascii("#.##\n...."), Default::default());
+        let mut isolated = 0;
+        for seed in 0..16 {
+            let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(seed);
+            let (nodes, _edges) = space.layout_with(2, Algorithm::AldousBroder, &mut rng).unwrap();
+            assert!(nodes[2].group.is_some() && nodes[3].group.is_some(), "seed {seed}");
+            assert_eq!(nodes[0].group.is_some(), nodes[0].root, "seed {seed}");
+            isolated += nodes[0].root as usize;
+        }
+        assert!(isolated > 0);
+    }
+
+    #[test]
+    fn test_kruskal() {
+        init_tests();
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(8, 8);
+        let (nodes, edges) = space.layout_with(3, Algorithm::Kruskal, &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group.is_some()));
+        assert!(edges.iter().all(|e| e.direction != EdgeDirection::Unknown));
+        let open = edges.iter().filter(|e| e.direction != EdgeDirection::Closed).count();
+        assert_eq!(open, space.num_nodes() - 3);
+        let (nodes, _edges) = Algorithm::Kruskal.generate(&space, 2, &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group.is_some()));
+
+        // A three by three room in the middle.
+        let room = (2..5).flat_map(|row| (2..5).map(move |col| row * 8 + col)).collect::<Vec<_>>();
+        let kruskal = Kruskal::new().with_room(room.clone());
+        let (nodes, mut edges) = space.layout_with(1, &kruskal, &mut rng).unwrap();
+        let inside = (0..space.num_edges())
+            .filter(|eid| space.edge_nodes(*eid).all(|nid| room.contains(&nid)))
+            .collect::<Vec<_>>();
+        assert_eq!(inside.len(), 12);
+        assert!(inside.iter().all(|eid| edges[*eid].direction != EdgeDirection::Closed));
+        assert_eq!(inside.iter().filter(|eid| edges[**eid].direction == EdgeDirection::Open).count(), 4);
+        let tree = edges.iter().filter(|e| matches!(e.direction, EdgeDirection::Forward | EdgeDirection::Backward)).count();
+        assert_eq!(tree, space.num_nodes() - 1);
+        let root = nodes.iter().position(|n| n.root).unwrap();
+        let far = if root == 63 { 0 } else { 63 };
+        assert!(space.solve(&mut edges, root, far).count() > 0);
+        assert!(space.layout_with(1, Kruskal::new().with_room(vec![64]), &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_growing_tree() {
+        init_tests();
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(20, 20);
+        let dead_ends = |edges: &[Edge]| (0..space.num_nodes())
+            .filter(|nid| space.node_edges(*nid).filter(|eid| edges[*eid].direction != EdgeDirection::Closed).count() == 1)
+            .count();
+        assert!(GrowingTree::mix(vec![]).is_err());
+        assert!(GrowingTree::mix(vec![(Selection::Newest, -1.0), (Selection::Random, 2.0)]).is_err());
+        let mut counts = Vec::new();
+        for tree in [
+            GrowingTree::new(Selection::Newest),
+            GrowingTree::mix(vec![(Selection::Newest, 0.75), (Selection::Random, 0.25)]).unwrap(),
+            GrowingTree::new(Selection::Random),
+        ] {
+            let (nodes, edges) = space.layout_with(2, &tree, &mut rng).unwrap();
+            assert!(nodes.iter().all(|n| n.group.is_some()));
+            assert!(edges.iter().all(|e| e.direction != EdgeDirection::Unknown));
+            let open = edges.iter().filter(|e| e.direction != EdgeDirection::Closed).count();
+            assert_eq!(open, space.num_nodes() - 2);
+            counts.push(dead_ends(&edges));
+        }
+        assert!(counts[0] < counts[1] && counts[1] < counts[2], "{counts:?}");
+        let (_nodes, edges) = space.layout_with(1, GrowingTree::new(Selection::Oldest), &mut rng).unwrap();
+        assert!(edges.iter().all(|e| e.direction != EdgeDirection::Unknown));
+    }
+
+    #[test]
+    fn test_weight() {
+        init_tests();
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(20, 20);
+        let east_west = |edges: &[Edge]| (0..space.num_edges())
+            .filter(|eid| edges[*eid].direction.is_open() && space.is_east_west(*eid))
+            .count();
+        let plain = GrowingTree::new(Selection::Newest);
+        let (_nodes, edges) = space.layout_with(1, &plain, &mut rng).unwrap();
+        let even = east_west(&edges);
+        let river = plain.clone().with_weight(|eid| if space.is_east_west(eid) { 3.0 } else { 1.0 });
+        let (nodes, edges) = space.layout_with(1, &river, &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group.is_some()));
+        assert!(east_west(&edges) > even + 50, "{} against {even}", east_west(&edges));
+
+        // Weighted zero, a direction is only carved where there is no choice.
+        let only = plain.with_weight(|eid| if space.is_east_west(eid) { 0.0 } else { 1.0 });
+        let (_nodes, edges) = space.layout_with(1, &only, &mut rng).unwrap();
+        let open = edges.iter().filter(|e| e.direction.is_open()).count();
+        assert_eq!(open, space.num_nodes() - 1);
+        assert!(east_west(&edges) < even, "{} against {even}", east_west(&edges));
+
+        // The default frontier flood takes the same weights.
+        let (_nodes, edges) = space.layout_with(1, Frontier::new(), &mut rng).unwrap();
+        let even = east_west(&edges);
+        let river = Frontier::new().with_weight(|eid| if space.is_east_west(eid) { 3.0 } else { 1.0 });
+        let (nodes, edges) = space.layout_with(2, &river, &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group.is_some()));
+        let open = edges.iter().filter(|e| e.direction.is_open()).count();
+        assert_eq!(open, space.num_nodes() - 2);
+        assert!(east_west(&edges) > even + 30, "{} against {even}", east_west(&edges));
+
+        // Equal weights give the same maze as the unweighted flood.
+        let flat = Frontier::new().with_weight(|_eid| 2.0);
+        let (_nodes, edges) = space.layout_with(1, &flat, &mut rng.clone()).unwrap();
+        let (_nodes, expected) = space.layout_with(1, Algorithm::Frontier, &mut rng).unwrap();
+        assert!(edges.iter().zip(&expected).all(|(a, b)| a.direction == b.direction));
+    }
+
+    #[test]
+    fn test_recursive_division() {
+        init_tests();
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(12, 16);
+        assert!(space.layout_with(1, RecursiveDivision::new(&SpaceSquare::new(3, 3), 1), &mut rng).is_err());
+        let woven = SpaceSquare::new(12, 16).with_weave(0.5, &mut rng);
+        assert!(woven.layout_with(1, RecursiveDivision::new(&woven, 1), &mut rng).is_err());
+        let masked = SpaceSquare::with_mask(crate::mask::Mask::from_ascii("#.##\n...."), crate::space_square::Wrap::None);
+        assert!(masked.layout_with(1, RecursiveDivision::new(&masked, 1), &mut rng).is_err());
+        // Made for a plain grid of the same size, but given another.
+        let plain = RecursiveDivision::new(&SpaceSquare::new(2, 4), 1);
+        assert!(masked.layout_with(1, &plain, &mut rng).is_err());
+        let plain = RecursiveDivision::new(&SpaceSquare::new(12, 16), 1);
+        assert!(woven.layout_with(1, &plain, &mut rng).is_err());
+        let wrapped = SpaceSquare::with_wrap(12, 16, crate::space_square::Wrap::Torus);
+        assert!(wrapped.layout_with(1, &plain, &mut rng).is_ok());
+
+        let (nodes, edges) = space.layout_with(2, RecursiveDivision::new(&space, 1), &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group.is_some()));
+        assert!(edges.iter().all(|e| !matches!(e.direction, EdgeDirection::Unknown | EdgeDirection::Open)));
+        let open = edges.iter().filter(|e| e.direction != EdgeDirection::Closed).count();
+        assert_eq!(open, space.num_nodes() - 2);
+
+        let (nodes, mut edges) = space.layout_with(1, RecursiveDivision::new(&space, 3), &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group == Some(0)));
+        assert!(edges.iter().any(|e| e.direction == EdgeDirection::Open));
+        let tree = edges.iter().filter(|e| matches!(e.direction, EdgeDirection::Forward | EdgeDirection::Backward)).count();
+        assert_eq!(tree, space.num_nodes() - 1);
+        let root = nodes.iter().position(|n| n.root).unwrap();
+        let far = if root == 0 { space.num_nodes() - 1 } else { 0 };
+        assert!(space.solve(&mut edges, root, far).count() > 0);
+    }
+}
@@ -1,6 +1,17 @@
 pub mod space;
+pub mod generator;
+pub mod eller;
 pub mod space_square;
+pub mod space_hex;
+pub mod space_polar;
+pub mod space_triangle;
+pub mod space_cube;
+pub mod space_graph;
+pub mod space_voronoi;
+pub mod space_sphere;
+pub mod mask;
 pub mod space_meta;
+pub mod lock;
 pub mod node;
 pub mod edge;
 pub mod error;
@@ -80,45 +91,108 @@ mod internal {
         render::*,
         space_square::*,
         space_meta::*,
+        lock::{Locks, render_locks_2d},
+        node::Node,
+        edge::{Edge, EdgeDirection},
+        error::{Error, Result},
     };
-    pub fn maze_square(seed: u64, width: usize, height: usize, scale: f32) -> (String, usize, usize) {
+    /// The roots on each level of a maze: six zones, joined by one.
+    pub const DEFAULT_LEVELS: [usize; 2] = [6, 1];
+
+    /// How many mazes to lay out looking for one that meets a target.
+    const TARGET_ATTEMPTS: usize = 50;
+    /// How many pairs of endpoints to try in each maze.
+    const TARGET_TRIES: usize = 200;
+
+    fn layout(rng: &mut impl rand::Rng, width: usize, height: usize, levels: &[usize]) -> Result<(SpaceSquare, Vec<Node>, Vec<Edge>)> {
+        let space = SpaceSquare::new(height, width);
+        let roots = levels.first().copied().unwrap_or(1);
+        let (nodes, edges) = space.layout(roots, rng)?;
+        Ok((space, nodes, edges))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render(space: &SpaceSquare, nodes: &[Node], edges: &mut [Edge], hierarchy: &mut Hierarchy, start: usize, end: usize, scale: f32, openings: bool) -> Result<(String, usize, usize)> {
+        let path = hierarchy.solve(space, nodes, edges, start, end)?;
+        let gateways = path.iter().filter(|eid| edges[**eid].direction == EdgeDirection::Border).count();
+        let solution_zones = gateways + 1;
+        let solution_length = path.len() - gateways;
+        let svg = if openings {
+            render_svg_2d_openings(space, edges, nodes, space.cols(), space.rows(), scale, start, end)
+        } else {
+            render_svg_2d(space, edges, nodes, space.cols(), space.rows(), scale, start, end)
+        };
+        Ok((format!("{}", svg), solution_zones, solution_length))
+    }
+
+    /// Lays out a square maze with a level of zones for each number of
+    /// roots in `levels`, lowest first, such as `[24, 6, 2, 1]`. The last
+    /// level must have one root.
+    pub fn maze_square(seed: u64, width: usize, height: usize, scale: f32, levels: &[usize]) -> Result<(String, usize, usize)> {
         use rand::SeedableRng;
         use rand_chacha::ChaCha12Rng;
         let mut rng = ChaCha12Rng::seed_from_u64(seed);
-        let space = SpaceSquare::new(height, width);
-        let (nodes, mut edges) = space.layout(6, &mut rng).unwrap();
-        let (start, end) = space.get_endpoints(&mut rng);
-        let end_zone = nodes[end].group.unwrap();
-        let mut meta = SpaceMeta::new(&space, &edges, &nodes);
-        let (_meta_nodes, mut meta_edges) = meta.layout(1, &mut rng).unwrap();
-        meta.open_gateways(&meta_edges, &mut edges, &mut rng);
-
-        let mut solution_zones = 1;
-        let solution_length = {
-            let mut solution_length = 0;
-            let mut start = start;
-            let mut start_zone = nodes[start].group.unwrap();
-            let last_end = end;
-            let meta_start = meta.zone_index(start_zone).unwrap();
-            let meta_end = meta.zone_index(end_zone).unwrap();
-            for zone in meta.solve(&mut meta_edges, meta_start, meta_end) {
-                let end_edge = meta.gateway(zone).unwrap();
-                edges[end_edge].solution = true;
-                let (end, new_start): (Vec<_>, Vec<_>) = space.edge_nodes(end_edge)
-                                       .map(|nid| (nid, nodes[nid].group.unwrap()))
-                                       .partition(|(_nid, gid)| *gid == start_zone);
-                let end = end.into_iter().next().unwrap();
-                let end = end.0;
-                let new_start = new_start.into_iter().next().unwrap();
-                solution_length += space.solve(&mut edges, start, end).count();
-                start = new_start.0;
-                start_zone = new_start.1;
-                solution_zones += 1;
+        let (space, nodes, mut edges) = layout(&mut rng, width, height, levels)?;
+        let (start, end) = space.get_endpoints(&mut rng)
+            .ok_or(Error::MessageError("fewer than two cells for the endpoints"))?;
+        let above = levels.get(1..).unwrap_or(&[]);
+        let mut hierarchy = Hierarchy::new(&space, &nodes, &mut edges, above, &mut rng)?;
+        render(&space, &nodes, &mut edges, &mut hierarchy, start, end, scale, false)
+    }
+
+    /// Like [`maze_square`], but lays out mazes and tries endpoints in them
+    /// until the solution meets the target.
+    pub fn maze_square_target(seed: u64, width: usize, height: usize, scale: f32, levels: &[usize], target: &Target) -> Result<(String, usize, usize)> {
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha12Rng;
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        let above = levels.get(1..).unwrap_or(&[]);
+        for _ in 0..TARGET_ATTEMPTS {
+            let (space, nodes, mut edges) = layout(&mut rng, width, height, levels)?;
+            let mut hierarchy = Hierarchy::new(&space, &nodes, &mut edges, above, &mut rng)?;
+            if let Some((start, end)) = hierarchy.find_endpoints(&space, &nodes, &edges, target, TARGET_TRIES, &mut rng) {
+                return render(&space, &nodes, &mut edges, &mut hierarchy, start, end, scale, false)
             }
-            solution_length + space.solve(&mut edges, start, last_end).count()
-        };
-        let svg = render_svg_2d(&space, &edges, &nodes, width, height, scale, start, end);
-        (format!("{}", svg), solution_zones, solution_length)
+        }
+        Err(Error::MessageError("no maze met the target"))
+    }
+
+    /// Like [`maze_square`], but picks the endpoints with a strategy once
+    /// the zones are joined. Border endpoints get openings in the outer
+    /// wall.
+    pub fn maze_square_endpoints(seed: u64, width: usize, height: usize, scale: f32, levels: &[usize], endpoints: Endpoints) -> Result<(String, usize, usize)> {
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha12Rng;
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        let (space, nodes, mut edges) = layout(&mut rng, width, height, levels)?;
+        let above = levels.get(1..).unwrap_or(&[]);
+        let mut hierarchy = Hierarchy::new(&space, &nodes, &mut edges, above, &mut rng)?;
+        let (start, end) = space.choose_endpoints(&hierarchy.top_nodes(&nodes), &edges, endpoints, &mut rng)
+            .ok_or(Error::MessageError("no endpoints fit the strategy"))?;
+        render(&space, &nodes, &mut edges, &mut hierarchy, start, end, scale, endpoints == Endpoints::Border)
+    }
+
+    /// Like [`maze_square`], but locks `locks` of the gateways and draws
+    /// the doors and their keys. The solution goes out of its way for the
+    /// keys, and counts every gateway it goes through.
+    pub fn maze_square_locks(seed: u64, width: usize, height: usize, scale: f32, levels: &[usize], locks: usize) -> Result<(String, usize, usize)> {
+        use rand::SeedableRng;
+        use rand_chacha::ChaCha12Rng;
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        let (space, nodes, mut edges) = layout(&mut rng, width, height, levels)?;
+        let (start, end) = space.get_endpoints(&mut rng)
+            .ok_or(Error::MessageError("fewer than two cells for the endpoints"))?;
+        let above = levels.get(1..).unwrap_or(&[]);
+        Hierarchy::new(&space, &nodes, &mut edges, above, &mut rng)?;
+        let locks = Locks::new(&space, &nodes, &edges, start, locks, &mut rng)?;
+        let path = locks.solve(&space, &mut edges, start, end)
+            .ok_or(Error::MessageError("the end cannot be reached"))?;
+        let gateways = path.iter().filter(|eid| edges[**eid].direction == EdgeDirection::Border).count();
+        let mut svg = render_svg_2d(&space, &edges, &nodes, space.cols(), space.rows(), scale, start, end);
+        for node in render_locks_2d(&space, locks.locks(), scale) {
+            svg = svg.add(node);
+        }
+        Ok((format!("{}", svg), gateways + 1, path.len() - gateways))
     }
 }
 
@@ -131,18 +205,88 @@ pub struct MazeSquareResult {
     pub solution_length: usize,
 }
 
+#[cfg(feature="wasm")]
+fn js_error(error: error::Error) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+#[cfg(feature="wasm")]
+#[wasm_bindgen]
+pub fn maze_square(seed: u64, width: usize, height: usize, scale: f32) -> Result<JsValue, JsValue> {
+    maze_square_levels(seed, width, height, scale, internal::DEFAULT_LEVELS.to_vec())
+}
+
 #[cfg(feature="wasm")]
 #[wasm_bindgen]
-pub fn maze_square(seed: u64, width: usize, height: usize, scale: f32) -> JsValue {
-    let (svg, solution_zones, solution_length) = internal::maze_square(seed, width, height, scale);
-    serde_wasm_bindgen::to_value(&MazeSquareResult {
+pub fn maze_square_levels(seed: u64, width: usize, height: usize, scale: f32, levels: Vec<usize>) -> Result<JsValue, JsValue> {
+    let (svg, solution_zones, solution_length) = internal::maze_square(seed, width, height, scale, &levels)
+        .map_err(js_error)?;
+    Ok(serde_wasm_bindgen::to_value(&MazeSquareResult {
         svg,
         solution_zones,
         solution_length,
-    }).unwrap()
+    })?)
+}
+
+#[cfg(feature="wasm")]
+#[wasm_bindgen]
+pub fn maze_square_target(seed: u64, width: usize, height: usize, scale: f32, levels: Vec<usize>, target: JsValue) -> Result<JsValue, JsValue> {
+    let target: space_meta::Target = serde_wasm_bindgen::from_value(target)?;
+    let (svg, solution_zones, solution_length) = internal::maze_square_target(seed, width, height, scale, &levels, &target)
+        .map_err(js_error)?;
+    Ok(serde_wasm_bindgen::to_value(&MazeSquareResult {
+        svg,
+        solution_zones,
+        solution_length,
+    })?)
+}
+
+#[cfg(feature="wasm")]
+#[wasm_bindgen]
+pub fn maze_square_endpoints(seed: u64, width: usize, height: usize, scale: f32, levels: Vec<usize>, endpoints: JsValue) -> Result<JsValue, JsValue> {
+    let endpoints: space::Endpoints = serde_wasm_bindgen::from_value(endpoints)?;
+    let (svg, solution_zones, solution_length) = internal::maze_square_endpoints(seed, width, height, scale, &levels, endpoints)
+        .map_err(js_error)?;
+    Ok(serde_wasm_bindgen::to_value(&MazeSquareResult {
+        svg,
+        solution_zones,
+        solution_length,
+    })?)
+}
+
+#[cfg(feature="wasm")]
+#[wasm_bindgen]
+pub fn maze_square_locks(seed: u64, width: usize, height: usize, scale: f32, levels: Vec<usize>, locks: usize) -> Result<JsValue, JsValue> {
+    let (svg, solution_zones, solution_length) = internal::maze_square_locks(seed, width, height, scale, &levels, locks)
+        .map_err(js_error)?;
+    Ok(serde_wasm_bindgen::to_value(&MazeSquareResult {
+        svg,
+        solution_zones,
+        solution_length,
+    })?)
+}
+
+#[cfg(not(feature="wasm"))]
+pub fn maze_square(seed: u64, width: usize, height: usize, scale: f32) -> error::Result<(String, usize, usize)> {
+    internal::maze_square(seed, width, height, scale, &internal::DEFAULT_LEVELS)
+}
+
+#[cfg(not(feature="wasm"))]
+pub fn maze_square_levels(seed: u64, width: usize, height: usize, scale: f32, levels: Vec<usize>) -> error::Result<(String, usize, usize)> {
+    internal::maze_square(seed, width, height, scale, &levels)
+}
+
+#[cfg(not(feature="wasm"))]
+pub fn maze_square_target(seed: u64, width: usize, height: usize, scale: f32, levels: Vec<usize>, target: space_meta::Target) -> error::Result<(String, usize, usize)> {
+    internal::maze_square_target(seed, width, height, scale, &levels, &target)
+}
+
+#[cfg(not(feature="wasm"))]
+pub fn maze_square_endpoints(seed: u64, width: usize, height: usize, scale: f32, levels: Vec<usize>, endpoints: space::Endpoints) -> error::Result<(String, usize, usize)> {
+    internal::maze_square_endpoints(seed, width, height, scale, &levels, endpoints)
 }
 
 #[cfg(not(feature="wasm"))]
-pub fn maze_square(seed: u64, width: usize, height: usize, scale: f32) -> (String, usize, usize) {
-    internal::maze_square(seed, width, height, scale)
+pub fn maze_square_locks(seed: u64, width: usize, height: usize, scale: f32, levels: Vec<usize>, locks: usize) -> error::Result<(String, usize, usize)> {
+    internal::maze_square_locks(seed, width, height, scale, &levels, locks)
 }
@@ -0,0 +1,273 @@
+use svg::node::{
+    Node as SvgNode,
+    element::Path,
+};
+use crate::{
+    edge::{Edge, EdgeDirection},
+    node::Node,
+    error::*,
+    space::*,
+    render::{SpaceRenderer, wall_2d, spot_2d},
+};
+
+/// How many colours the page has for doors and their keys. Further locks
+/// reuse them in turn.
+pub const LOCK_COLOURS: usize = 4;
+
+/// A gateway that is locked, and the node its key lies on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lock {
+    /// The gateway edge the lock is on.
+    pub door: usize,
+    /// The node the key lies on.
+    pub key: usize,
+}
+
+/// Locked doors on some of the gateways that [`crate::space_meta::Hierarchy`]
+/// opened between zones, with the key to each in a zone on the way from the
+/// start to its door.
+pub struct Locks {
+    locks: Vec<Lock>,
+}
+
+/// Spreads out from the nodes on `visiting` through every open edge that
+/// `passable` allows, marking each node it reaches with the node it came
+/// from.
+fn flood(space: &impl Space, edges: &[Edge], came_from: &mut [Option<usize>], visiting: &mut Vec<usize>, passable: impl Fn(usize) -> bool) {
+    while let Some(nid) = visiting.pop() {
+        for eid in space.node_edges(nid).filter(|eid| edges[*eid].direction.is_open() && passable(*eid)) {
+            let other = follow_edge(space, nid, eid).unwrap();
+            if came_from[other].is_none() {
+                came_from[other] = Some(nid);
+                visiting.push(other);
+            }
+        }
+    }
+}
+
+impl Locks {
+    /// Locks `count` of the gateways at random. Starting from `start`,
+    /// each time the maze that can be reached comes up against a locked
+    /// door, the key to that door is left in one of the zones passed
+    /// through on the way from the start to the door, so every door can be
+    /// opened in turn.
+    pub fn new(space: &impl Space, nodes: &[Node], edges: &[Edge], start: usize, count: usize, rng: &mut impl rand::Rng) -> Result<Self> {
+        use rand::seq::SliceRandom;
+        let mut doors = (0..edges.len())
+            .filter(|eid| edges[*eid].direction == EdgeDirection::Border)
+            .collect::<Vec<_>>();
+        if count > doors.len() {
+            return Err(Error::MessageError("more locks than gateways"))
+        }
+        doors.shuffle(rng);
+        doors.truncate(count);
+
+        let mut locked = vec![false; edges.len()];
+        doors.iter().for_each(|door| locked[*door] = true);
+        // The start comes from itself, so it counts as reached.
+        let mut came_from = vec![None; space.num_nodes()];
+        let mut taken = vec![false; space.num_nodes()];
+        let mut visiting = vec![start];
+        came_from[start] = Some(start);
+        taken[start] = true;
+        let mut locks = Vec::with_capacity(count);
+        loop {
+            flood(space, edges, &mut came_from, &mut visiting, |eid| !locked[eid]);
+            let waiting = doors.iter()
+                .filter(|door| locked[**door] && space.edge_nodes(**door).any(|nid| came_from[nid].is_some()))
+                .copied()
+                .collect::<Vec<_>>();
+            if waiting.is_empty() {
+                break
+            }
+            let door = waiting[rng.random_range(0..waiting.len())];
+            let near = space.edge_nodes(door).find(|nid| came_from[*nid].is_some()).unwrap();
+            let mut zones = vec![nodes[near].group];
+            let mut nid = near;
+            while nid != start {
+                nid = came_from[nid].unwrap();
+                if !zones.contains(&nodes[nid].group) {
+                    zones.push(nodes[nid].group);
+                }
+            }
+            let spots = (0..space.num_nodes())
+                .filter(|nid| came_from[*nid].is_some() && !taken[*nid] && zones.contains(&nodes[*nid].group))
+                .collect::<Vec<_>>();
+            if spots.is_empty() {
+                return Err(Error::MessageError("no room for a key before its door"))
+            }
+            let key = spots[rng.random_range(0..spots.len())];
+            taken[key] = true;
+            locked[door] = false;
+            locks.push(Lock{
+                door,
+                key,
+            });
+            for nid in space.edge_nodes(door) {
+                if came_from[nid].is_none() {
+                    came_from[nid] = Some(near);
+                    visiting.push(nid);
+                }
+            }
+        }
+        Ok(Self{
+            locks,
+        })
+    }
+
+    /// The locks, in the order their doors are first reached from the
+    /// start.
+    pub fn locks(&self) -> &[Lock] {
+        &self.locks
+    }
+
+    /// Walks from `start` to `end`, going out of the way to pick up each
+    /// key that is needed, nearest first. Marks the solution and returns
+    /// every edge walked in order, so edges on a detour appear twice.
+    /// `None` if `end` cannot be reached.
+    pub fn solve(&self, space: &impl Space, edges: &mut [Edge], start: usize, end: usize) -> Option<Vec<usize>> {
+        let mut held = vec![false; self.locks.len()];
+        let mut path = Vec::new();
+        let mut at = start;
+        loop {
+            let open = held.clone();
+            let passable = |eid: usize| self.locks.iter().zip(open.iter()).all(|(lock, held)| lock.door != eid || *held);
+            let (walk, to) = match search(space, edges, at, end, passable) {
+                Some(walk) => (walk, end),
+                None => self.locks.iter().zip(held.iter())
+                    .filter(|(_lock, held)| !**held)
+                    .filter_map(|(lock, _held)| Some((search(space, edges, at, lock.key, passable)?, lock.key)))
+                    .min_by_key(|(walk, _key)| walk.len())?,
+            };
+            for eid in walk.iter() {
+                at = follow_edge(space, at, *eid).unwrap();
+                self.pick_up(at, &mut held);
+            }
+            self.pick_up(to, &mut held);
+            path.extend(walk);
+            if to == end {
+                break
+            }
+        }
+        path.iter().for_each(|eid| edges[*eid].solution = true);
+        Some(path)
+    }
+
+    #[inline]
+    fn pick_up(&self, node_id: usize, held: &mut [bool]) {
+        for (lock, held) in self.locks.iter().zip(held.iter_mut()) {
+            if lock.key == node_id {
+                *held = true;
+            }
+        }
+    }
+}
+
+
+/// A door across each locked gateway and a key on the node it lies on,
+/// with ids `door_N` and `key_N` for lock `N`, to add to a rendered maze.
+/// Both are classed `lock_C`, cycling through [`LOCK_COLOURS`], so each key
+/// can be styled to match its door.
+pub fn render_locks_2d<'a>(space: &'a impl SpaceRenderer<2>, locks: &'a [Lock], scale: f32) -> impl Iterator<Item=Box<dyn SvgNode>> + 'a {
+    locks.iter().enumerate()
+        .flat_map(move |(lid, lock)| {
+            let colour = lid % LOCK_COLOURS;
+            let door: Box<dyn SvgNode> = Box::new(
+                Path::new()
+                    .set("id", format!("door_{lid}"))
+                    .set("class", format!("door lock_{colour}"))
+                    .set("d", wall_2d(space, lock.door, scale))
+            );
+            let key: Box<dyn SvgNode> = Box::new(
+                spot_2d(space, lock.key, scale, &format!("key lock_{colour}"))
+                    .set("id", format!("key_{lid}"))
+                    .set("r", scale * 0.3)
+            );
+            [door, key]
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::space_square::SpaceSquare;
+    use crate::space_meta::Hierarchy;
+    use super::*;
+    use rand::SeedableRng;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_locks() {
+        init_tests();
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(20, 20);
+        let (nodes, mut edges) = space.layout(8, &mut rng).unwrap();
+        Hierarchy::new(&space, &nodes, &mut edges, &[1], &mut rng).unwrap();
+        assert!(Locks::new(&space, &nodes, &edges, 0, 8, &mut rng).is_err());
+        let locks = Locks::new(&space, &nodes, &edges, 0, 4, &mut rng).unwrap();
+        assert_eq!(locks.locks().len(), 4);
+
+        // Each key can be reached with the keys before it, without going
+        // through its own door.
+        for (lid, lock) in locks.locks().iter().enumerate() {
+            let later = &locks.locks()[lid..];
+            let passable = |eid: usize| later.iter().all(|lock| lock.door != eid);
+            assert!(search(&space, &edges, 0, lock.key, passable).is_some(), "key {lid} is behind a door");
+        }
+
+        // The zones make a tree, so the zones on the way to each door are
+        // the ones the only path to its near side goes through.
+        for (lid, lock) in locks.locks().iter().enumerate() {
+            let way = space.edge_nodes(lock.door)
+                .filter_map(|nid| search(&space, &edges, 0, nid, |eid| eid != lock.door))
+                .min_by_key(|way| way.len())
+                .unwrap();
+            let zones = way.iter()
+                .fold(vec![nodes[0].group], |mut zones, eid| {
+                    zones.extend(space.edge_nodes(*eid).map(|nid| nodes[nid].group));
+                    zones
+                });
+            assert!(zones.contains(&nodes[lock.key].group), "key {lid} is off the way to its door");
+        }
+
+        let end = space.num_nodes() - 1;
+        let path = locks.solve(&space, &mut edges, 0, end).unwrap();
+        let mut held = Vec::new();
+        let mut nid = 0;
+        for eid in path.iter() {
+            assert!(edges[*eid].direction.is_open() && edges[*eid].solution, "edge {eid}");
+            if let Some(lock) = locks.locks().iter().find(|lock| lock.door == *eid) {
+                assert!(held.contains(&lock.key), "door {eid} opened without its key");
+            }
+            nid = follow_edge(&space, nid, *eid).unwrap();
+            held.push(nid);
+        }
+        assert_eq!(nid, end);
+        assert!(path.len() >= space.solve_search(&mut edges, 0, end).unwrap().len());
+
+        let mut svg = crate::render::render_svg_2d(&space, &edges, &nodes, 20, 20, 10.0, 0, end);
+        for node in render_locks_2d(&space, locks.locks(), 10.0) {
+            svg = svg.add(node);
+        }
+        let svg = svg.to_string();
+        assert_eq!(svg.matches("class=\"door lock_").count(), 4);
+        assert_eq!(svg.matches("class=\"key lock_").count(), 4);
+        for lid in 0..4 {
+            assert!(svg.contains(&format!("id=\"door_{lid}\"")) && svg.contains(&format!("id=\"key_{lid}\"")));
+        }
+        // Locks past the last colour reuse the first.
+        let many = (0..LOCK_COLOURS + 1).map(|_| locks.locks()[0]).collect::<Vec<_>>();
+        let classes = render_locks_2d(&space, &many, 10.0)
+            .map(|node| node.get_attributes().unwrap()["class"].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(classes[2 * LOCK_COLOURS], "door lock_0");
+        assert_eq!(classes[2 * LOCK_COLOURS + 1], "key lock_0");
+    }
+}
@@ -0,0 +1,186 @@
+use crate::error::*;
+
+/// Which cells of a rectangular grid are part of the maze, so that a maze
+/// can take the shape of a letter, a logo or a silhouette.
+#[derive(Debug, Clone)]
+pub struct Mask {
+    rows: usize,
+    cols: usize,
+    cells: Vec<bool>,
+}
+
+impl Mask {
+    /// A mask with every cell enabled.
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self{
+            rows,
+            cols,
+            cells: vec![true; rows * cols],
+        }
+    }
+
+    /// A mask from rows of cells, where `true` is enabled. Short rows are
+    /// padded with disabled cells.
+    pub fn from_grid(grid: &[Vec<bool>]) -> Self {
+        let rows = grid.len();
+        let cols = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut cells = vec![false; rows * cols];
+        for (r, row) in grid.iter().enumerate() {
+            cells[r * cols..r * cols + row.len()].copy_from_slice(row);
+        }
+        Self{
+            rows,
+            cols,
+            cells,
+        }
+    }
+
+    /// A mask from ASCII art, one line per row, where spaces and `.` are
+    /// disabled and any other character is enabled.
+    pub fn from_ascii(text: &str) -> Self {
+        let grid = text.lines()
+            .map(|line| line.chars().map(|c| c != ' ' && c != '.').collect())
+            .collect::<Vec<Vec<bool>>>();
+        Self::from_grid(&grid)
+    }
+
+    /// A mask from raw brightness bytes, one per pixel, row by row. Pixels
+    /// darker than half brightness, below `0x80`, are enabled, and the rest
+    /// disabled. See [`Mask::from_png`] to read an image file.
+    pub fn from_luma(width: usize, height: usize, pixels: &[u8]) -> Result<Self> {
+        if pixels.len() != width * height {
+            return Err(Error::MessageError("pixel buffer does not match the image size"))
+        }
+        Ok(Self{
+            rows: height,
+            cols: width,
+            cells: pixels.iter().map(|p| *p < 0x80).collect(),
+        })
+    }
+
+    /// A mask from the bytes of a PNG image, one cell per pixel, enabled
+    /// where the pixel is dark as in [`Mask::from_luma`]. Transparent
+    /// pixels are disabled.
+    pub fn from_png(bytes: &[u8]) -> Result<Self> {
+        let mut decoder = png::Decoder::new(std::io::Cursor::new(bytes));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()
+            .map_err(|_e| Error::MessageError("the image is not a PNG that can be read"))?;
+        let mut buffer = vec![0; reader.output_buffer_size().ok_or(Error::MessageError("the image is too big"))?];
+        let info = reader.next_frame(&mut buffer)
+            .map_err(|_e| Error::MessageError("the image is not a PNG that can be read"))?;
+        let channels = info.color_type.samples();
+        let (width, height) = (info.width as usize, info.height as usize);
+        let luma = buffer.chunks(info.line_size)
+            .take(height)
+            .flat_map(|line| line.chunks(channels).take(width))
+            .map(|pixel| match pixel {
+                [_, alpha] | [_, _, _, alpha] if *alpha < 0x80 => 0xff,
+                [luma] | [luma, _] => *luma,
+                [r, g, b] | [r, g, b, _] => ((*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000) as u8,
+                _ => 0xff,
+            })
+            .collect::<Vec<_>>();
+        Self::from_luma(width, height, &luma)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn is_enabled(&self, row: usize, col: usize) -> bool {
+        row < self.rows && col < self.cols && self.cells[row * self.cols + col]
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, enabled: bool) {
+        self.cells[row * self.cols + col] = enabled;
+    }
+
+    /// How many cells are enabled.
+    pub fn count(&self) -> usize {
+        self.cells.iter().filter(|c| **c).count()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    fn png(width: u32, height: u32, color: png::ColorType, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_from_grid() {
+        init_tests();
+        let mask = Mask::from_grid(&[vec![true, false, true], vec![false]]);
+        assert_eq!((mask.rows(), mask.cols()), (2, 3));
+        assert!(mask.is_enabled(0, 0) && !mask.is_enabled(0, 1) && mask.is_enabled(0, 2));
+        // The short row is padded with disabled cells.
+        assert!(!mask.is_enabled(1, 0) && !mask.is_enabled(1, 2));
+        assert!(!mask.is_enabled(0, 3) && !mask.is_enabled(2, 0));
+        assert_eq!(mask.count(), 2);
+    }
+
+    #[test]
+    fn test_from_ascii() {
+        init_tests();
+        let mask = Mask::from_ascii("#.x\n #\n");
+        assert_eq!((mask.rows(), mask.cols()), (2, 3));
+        assert!(mask.is_enabled(0, 0) && !mask.is_enabled(0, 1) && mask.is_enabled(0, 2));
+        assert!(!mask.is_enabled(1, 0) && mask.is_enabled(1, 1) && !mask.is_enabled(1, 2));
+        assert_eq!(Mask::from_ascii("").count(), 0);
+    }
+
+    #[test]
+    fn test_from_luma() {
+        init_tests();
+        let mask = Mask::from_luma(4, 1, &[0x00, 0x7f, 0x80, 0xff]).unwrap();
+        assert_eq!((mask.rows(), mask.cols()), (1, 4));
+        assert!(mask.is_enabled(0, 0) && mask.is_enabled(0, 1));
+        assert!(!mask.is_enabled(0, 2) && !mask.is_enabled(0, 3));
+        assert!(Mask::from_luma(2, 2, &[0; 3]).is_err());
+    }
+
+    #[test]
+    fn test_from_png() {
+        init_tests();
+        let grey = png(3, 2, png::ColorType::Grayscale, &[0x00, 0xff, 0x10, 0x90, 0x7f, 0xff]);
+        let mask = Mask::from_png(&grey).unwrap();
+        assert_eq!((mask.rows(), mask.cols()), (2, 3));
+        assert!(mask.is_enabled(0, 0) && !mask.is_enabled(0, 1) && mask.is_enabled(0, 2));
+        assert!(!mask.is_enabled(1, 0) && mask.is_enabled(1, 1) && !mask.is_enabled(1, 2));
+
+        // Black, white, dark red, and black but transparent.
+        let rgba = png(2, 2, png::ColorType::Rgba, &[
+            0, 0, 0, 0xff,  0xff, 0xff, 0xff, 0xff,
+            0x80, 0, 0, 0xff,  0, 0, 0, 0,
+        ]);
+        let mask = Mask::from_png(&rgba).unwrap();
+        assert!(mask.is_enabled(0, 0) && !mask.is_enabled(0, 1));
+        assert!(mask.is_enabled(1, 0) && !mask.is_enabled(1, 1));
+
+        assert!(Mask::from_png(b"not a png").is_err());
+    }
+}
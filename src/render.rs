@@ -1,6 +1,7 @@
 use svg::{
     Document,
     node::{
+        Node as SvgNode,
         element::{
             Path, Circle, Rectangle,
             path::{Data,Number},
@@ -12,8 +13,59 @@ use crate::node::*;
 use std::iter::Iterator;
 
 pub trait SpaceRenderer<const DIMS: usize> {
+    // Required Methods
     fn edge_position(&self, edge_id: usize) -> ([f32; DIMS], [f32; DIMS]);
     fn node_position(&self, node_id: usize) -> [f32; DIMS];
+
+    // Provided Methods
+
+    /// The wall segment separating the two nodes of an edge. `None` means
+    /// the wall is the edge itself turned a quarter turn about its middle,
+    /// which is right for square cells.
+    fn wall_position(&self, _edge_id: usize) -> Option<([f32; DIMS], [f32; DIMS])> {
+        None
+    }
+
+    /// The radius of the circle a wall is drawn along, clockwise about the
+    /// origin. `None` means the wall is a straight line.
+    fn wall_radius(&self, _edge_id: usize) -> Option<f32> {
+        None
+    }
+
+    /// Where a space wraps round, the copy of an edge's wall on the far
+    /// side of the space. `None` means the wall is only drawn once.
+    fn wrap_position(&self, _edge_id: usize) -> Option<([f32; DIMS], [f32; DIMS])> {
+        None
+    }
+
+    /// How the nodes of an edge are laid out. Walls across an edge that
+    /// passes under another node are drawn on either side of that node.
+    fn edge_kind(&self, _edge_id: usize) -> EdgeKind {
+        EdgeKind::Adjacent
+    }
+
+    /// The corners of the cell drawn for a node. `None` means a unit square.
+    fn node_outline(&self, _node_id: usize) -> Option<Vec<[f32; DIMS]>> {
+        None
+    }
+
+    /// The part of the outer wall beside a node on the border, where an
+    /// opening is cut for an entrance or exit. `None` means the node is not
+    /// on the border.
+    fn border_opening(&self, _node_id: usize) -> Option<([f32; DIMS], [f32; DIMS])> {
+        None
+    }
+
+    /// The walls around the outside of the space. `None` means a rectangle
+    /// the size of the document.
+    fn border_walls(&self) -> Option<Vec<([f32; DIMS], [f32; DIMS])>> {
+        None
+    }
+}
+
+#[inline]
+fn point(p: [f32; 2], scale: f32) -> (Number, Number) {
+    (scale * p[1] + 0.5 * scale, scale * p[0] + 0.5 * scale)
 }
 
 #[inline]
@@ -42,33 +94,81 @@ fn shift(points: ([f32; 2], [f32; 2]), scale: f32) -> ((Number, Number), (Number
     ((start_x, start_y), (end_x, end_y))
 }
 
-fn walls_2d(space: &impl SpaceRenderer<2>, edges: &[Edge], scale: f32) -> impl Iterator<Item=Path> {
-    edges.iter().enumerate()
+#[inline]
+fn wall_class(direction: EdgeDirection) -> Option<&'static str> {
+    match direction {
+        EdgeDirection::Forward | EdgeDirection::Backward | EdgeDirection::Open | EdgeDirection::Unknown => None,
+        EdgeDirection::Closed => Some("wall"),
+        EdgeDirection::Border => Some("gateway"),
+    }
+}
+
+/// The two walls on either side of the node an edge tunnels under. They
+/// are walls if the tunnel is closed, or the mouths of the tunnel if not.
+fn tunnel_2d(space: &impl SpaceRenderer<2>, edge_id: usize, e: &Edge, scale: f32) -> Path {
+    let (start, end) = space.edge_position(edge_id);
+    let mid = [(start[0] + end[0]) / 2.0, (start[1] + end[1]) / 2.0];
+    let (a, b) = shift((start, mid), scale);
+    let (c, d) = shift((mid, end), scale);
+    Path::new()
+        .set("id", format!("wall_{edge_id}"))
+        .set("class", match e.direction {
+            EdgeDirection::Closed => "wall",
+            EdgeDirection::Border => "tunnel gateway",
+            _ => "tunnel",
+        })
+        .set("d",
+            Data::new()
+            .move_to(a)
+            .line_to(b)
+            .move_to(c)
+            .line_to(d)
+        )
+}
+
+/// The path of the wall across an edge, as a line or an arc.
+pub fn wall_2d(space: &impl SpaceRenderer<2>, edge_id: usize, scale: f32) -> Data {
+    let (start, end) = match space.wall_position(edge_id) {
+        Some((start, end)) => (point(start, scale), point(end, scale)),
+        None => shift(space.edge_position(edge_id), scale),
+    };
+    let data = Data::new().move_to(start);
+    match space.wall_radius(edge_id) {
+        // Every arc sweeps clockwise, and none of them is more than half a
+        // turn.
+        Some(radius) => data.elliptical_arc_to((
+            radius * scale, radius * scale,
+            0, 0, 1,
+            end.0, end.1,
+        )),
+        None => data.line_to(end),
+    }
+}
+
+fn walls_2d<'a>(space: &impl SpaceRenderer<2>, edges: impl Iterator<Item=(usize, &'a Edge)>, scale: f32) -> impl Iterator<Item=Path> {
+    edges
         .filter_map(move |(eid, e)| {
-            match e.direction {
-                EdgeDirection::Forward | EdgeDirection::Backward | EdgeDirection::Unknown => return None,
-                _ => {}
+            if space.edge_kind(eid) == EdgeKind::Under && e.direction != EdgeDirection::Unknown {
+                return Some(tunnel_2d(space, eid, e, scale))
+            }
+            let class = wall_class(e.direction)?;
+            let mut data = wall_2d(space, eid, scale);
+            if let Some((start, end)) = space.wrap_position(eid) {
+                data = data
+                    .move_to(point(start, scale))
+                    .line_to(point(end, scale));
             }
-            let (start, end) = shift(space.edge_position(eid), scale);
             Some(
                 Path::new()
                 .set("id", format!("wall_{eid}"))
-                .set("class", match e.direction {
-                    EdgeDirection::Closed => "wall",
-                    EdgeDirection::Border => "gateway",
-                    _ => "ghost-wall",
-                })
-                .set("d", 
-                    Data::new()
-                    .move_to(start)
-                    .line_to(end)
-                )
+                .set("class", class)
+                .set("d", data)
             )
         })
 }
 
-fn solution_2d(space: &impl SpaceRenderer<2>, edges: &[Edge], scale: f32) -> impl Iterator<Item=Circle> {
-    edges.iter().enumerate()
+fn solution_2d<'a>(space: &impl SpaceRenderer<2>, edges: impl Iterator<Item=(usize, &'a Edge)>, scale: f32) -> impl Iterator<Item=Circle> {
+    edges
         .filter_map(move |(eid, e)| {
             if e.direction == EdgeDirection::Closed {
                 return None
@@ -88,31 +188,117 @@ fn solution_2d(space: &impl SpaceRenderer<2>, edges: &[Edge], scale: f32) -> imp
         })
 }
 
-fn node(space: &impl SpaceRenderer<2>, node_id: usize, scale: f32, class: impl std::fmt::Display) -> Rectangle {
+fn node(space: &impl SpaceRenderer<2>, node_id: usize, scale: f32, class: impl std::fmt::Display) -> Box<dyn SvgNode> {
+    if let Some(outline) = space.node_outline(node_id) {
+        let mut corners = outline.into_iter().map(|p| point(p, scale));
+        let mut data = Data::new();
+        if let Some(first) = corners.next() {
+            data = data.move_to(first);
+        }
+        for corner in corners {
+            data = data.line_to(corner);
+        }
+        return Box::new(
+            Path::new()
+                .set("class", class.to_string())
+                .set("d", data.close())
+        )
+    }
     let pos = space.node_position(node_id);
     let x = pos[1];
     let y = pos[0];
     let x = scale * x + 0.5 * scale;
     let y = scale * y + 0.5 * scale;
-    Rectangle::new()
-        .set("class", class.to_string())
-        .set("x", x - 0.5 * scale)
-        .set("y", y - 0.5 * scale)
-        .set("width", 1.0 * scale)
-        .set("height", 1.0 * scale)
+    Box::new(
+        Rectangle::new()
+            .set("class", class.to_string())
+            .set("x", x - 0.5 * scale)
+            .set("y", y - 0.5 * scale)
+            .set("width", 1.0 * scale)
+            .set("height", 1.0 * scale)
+    )
 }
 
 pub fn render_nodes_2d(
     space: &impl SpaceRenderer<2>,
     nodes: &[Node],
     scale: f32,
-) -> impl Iterator<Item=Rectangle> {
+) -> impl Iterator<Item=Box<dyn SvgNode>> {
     nodes.iter().enumerate()
         .map(move |(nid, n)| {
             node(space, nid, scale, format!("node node_group_{}{}", n.group.unwrap_or(usize::MAX), if n.root { " node_root" } else { "" }))
         })
 }
 
+/// What is left of a wall once the part along `opening` is cut out of it.
+fn cut_wall(wall: ([f32; 2], [f32; 2]), opening: ([f32; 2], [f32; 2])) -> Vec<([f32; 2], [f32; 2])> {
+    const EPSILON: f32 = 1e-4;
+    let (start, end) = wall;
+    let along = [end[0] - start[0], end[1] - start[1]];
+    let length2 = along[0] * along[0] + along[1] * along[1];
+    let cross = |p: [f32; 2]| (p[0] - start[0]) * along[1] - (p[1] - start[1]) * along[0];
+    let param = |p: [f32; 2]| ((p[0] - start[0]) * along[0] + (p[1] - start[1]) * along[1]) / length2;
+    if length2 < EPSILON || cross(opening.0).abs() > EPSILON || cross(opening.1).abs() > EPSILON {
+        return vec![wall]
+    }
+    let (a, b) = (param(opening.0), param(opening.1));
+    let (a, b) = (a.min(b).max(0.0), a.max(b).min(1.0));
+    if b - a < EPSILON {
+        return vec![wall]
+    }
+    let at = |t: f32| [start[0] + along[0] * t, start[1] + along[1] * t];
+    [(0.0, a), (b, 1.0)].into_iter()
+        .filter(|(from, to)| to - from > EPSILON)
+        .map(|(from, to)| (at(from), at(to)))
+        .collect()
+}
+
+/// The outer walls, with an opening cut beside each of `openings`.
+fn border_2d(space: &impl SpaceRenderer<2>, width: usize, height: usize, scale: f32, openings: &[usize]) -> Vec<Box<dyn SvgNode>> {
+    let cuts = openings.iter()
+        .filter_map(|nid| space.border_opening(*nid))
+        .collect::<Vec<_>>();
+    let walls = match space.border_walls() {
+        Some(walls) => walls,
+        None if cuts.is_empty() => return vec![
+            Box::new(
+                Rectangle::new()
+                    .set("class", "wall")
+                    .set("x", 0)
+                    .set("y", 0)
+                    .set("width", width as f32*scale)
+                    .set("height", height as f32*scale)
+            )
+        ],
+        None => {
+            let (bottom, right) = (height as f32 - 0.5, width as f32 - 0.5);
+            vec![
+                ([-0.5, -0.5], [-0.5, right]),
+                ([-0.5, right], [bottom, right]),
+                ([bottom, right], [bottom, -0.5]),
+                ([bottom, -0.5], [-0.5, -0.5]),
+            ]
+        }
+    };
+    walls.into_iter()
+        .flat_map(|wall| cuts.iter().fold(vec![wall], |walls, cut| {
+            walls.into_iter().flat_map(|wall| cut_wall(wall, *cut)).collect()
+        }))
+        .map(|(start, end)| -> Box<dyn SvgNode> {
+            Box::new(
+                Path::new()
+                    .set("class", "wall")
+                    .set("d",
+                        Data::new()
+                        .move_to(point(start, scale))
+                        .line_to(point(end, scale))
+                    )
+            )
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn render_svg_2d(
     space: &impl SpaceRenderer<2>,
     edges: &[Edge],
@@ -122,6 +308,38 @@ pub fn render_svg_2d(
     scale: f32,
     start: usize,
     end: usize,
+) -> Document {
+    svg_2d(space, edges, nodes, width, height, scale, start, end, &[])
+}
+
+/// Like [`render_svg_2d`], but with an entrance and exit cut into the outer
+/// wall beside `start` and `end`, for endpoints on the border such as those
+/// from [`crate::space::Endpoints::Border`].
+#[allow(clippy::too_many_arguments)]
+pub fn render_svg_2d_openings(
+    space: &impl SpaceRenderer<2>,
+    edges: &[Edge],
+    nodes: &[Node],
+    width: usize,
+    height: usize,
+    scale: f32,
+    start: usize,
+    end: usize,
+) -> Document {
+    svg_2d(space, edges, nodes, width, height, scale, start, end, &[start, end])
+}
+
+#[allow(clippy::too_many_arguments)]
+fn svg_2d(
+    space: &impl SpaceRenderer<2>,
+    edges: &[Edge],
+    nodes: &[Node],
+    width: usize,
+    height: usize,
+    scale: f32,
+    start: usize,
+    end: usize,
+    openings: &[usize],
 ) -> Document {
     let mut doc = Document::new()
         .set("viewBox", (-0.5 * scale, -0.5 * scale, (width+2) as f32 *scale, (height+2) as f32*scale))
@@ -131,20 +349,190 @@ pub fn render_svg_2d(
     }
     doc = doc.add(node(space, start, scale, "start"))
         .add(node(space, end, scale, "end"));
-    for wall in walls_2d(space, edges, scale) {
+    for wall in walls_2d(space, edges.iter().enumerate(), scale) {
         doc = doc.add(wall);
     }
-    for dot in solution_2d(space, edges, scale) {
+    for dot in solution_2d(space, edges.iter().enumerate(), scale) {
         doc = doc.add(dot);
     }
 
+    for wall in border_2d(space, width, height, scale, openings) {
+        doc = doc.add(wall);
+    }
+
     doc
-        .add(
+}
+
+fn passages_2d<'a>(space: &impl SpaceRenderer<2>, edges: impl Iterator<Item=(usize, &'a Edge)>, scale: f32) -> impl Iterator<Item=Path> {
+    edges
+        .filter_map(move |(eid, e)| {
+            let class = match e.direction {
+                EdgeDirection::Forward | EdgeDirection::Backward | EdgeDirection::Open => "passage",
+                EdgeDirection::Border => "passage gateway_passage",
+                EdgeDirection::Closed | EdgeDirection::Unknown => return None,
+            };
+            let (start, end) = space.edge_position(eid);
+            Some(
+                Path::new()
+                .set("id", format!("passage_{eid}"))
+                .set("class", class)
+                .set("d",
+                    Data::new()
+                    .move_to(point(start, scale))
+                    .line_to(point(end, scale))
+                )
+            )
+        })
+}
+
+/// A dot on a node.
+pub fn spot_2d(space: &impl SpaceRenderer<2>, node_id: usize, scale: f32, class: &str) -> Circle {
+    let (cx, cy) = point(space.node_position(node_id), scale);
+    Circle::new()
+        .set("class", class)
+        .set("cx", cx)
+        .set("cy", cy)
+        .set("r", scale * 0.4)
+}
+
+/// Draws the open passages of a maze rather than its walls, for spaces
+/// such as graphs where the nodes are not cells that tile the plane.
+#[allow(clippy::too_many_arguments)]
+pub fn render_svg_passages(
+    space: &impl SpaceRenderer<2>,
+    edges: &[Edge],
+    nodes: &[Node],
+    width: usize,
+    height: usize,
+    scale: f32,
+    start: usize,
+    end: usize,
+) -> Document {
+    let mut doc = Document::new()
+        .set("viewBox", (-0.5 * scale, -0.5 * scale, (width+2) as f32 *scale, (height+2) as f32*scale))
+    ;
+    for passage in passages_2d(space, edges.iter().enumerate(), scale) {
+        doc = doc.add(passage);
+    }
+    for (nid, n) in nodes.iter().enumerate() {
+        doc = doc.add(spot_2d(space, nid, scale, &format!("node node_group_{}{}", n.group.unwrap_or(usize::MAX), if n.root { " node_root" } else { "" })));
+    }
+    doc = doc.add(spot_2d(space, start, scale, "start"))
+        .add(spot_2d(space, end, scale, "end"));
+    for dot in solution_2d(space, edges.iter().enumerate(), scale) {
+        doc = doc.add(dot);
+    }
+
+    doc
+}
+
+/// Lays the floors of a 3D space out side by side, `stride` cells apart,
+/// where the first coordinate of a 3D position is its floor.
+struct Floors<'a, S> {
+    space: &'a S,
+    stride: f32,
+}
+
+impl<S> Floors<'_, S> {
+    #[inline]
+    fn flatten(&self, p: [f32; 3]) -> [f32; 2] {
+        [p[1], p[2] + p[0] * self.stride]
+    }
+}
+
+impl<S: SpaceRenderer<3>> SpaceRenderer<2> for Floors<'_, S> {
+    fn edge_position(&self, edge_id: usize) -> ([f32; 2], [f32; 2]) {
+        let (start, end) = self.space.edge_position(edge_id);
+        (self.flatten(start), self.flatten(end))
+    }
+    fn node_position(&self, node_id: usize) -> [f32; 2] {
+        self.flatten(self.space.node_position(node_id))
+    }
+}
+
+/// A marker pointing up or down the stairs between two floors.
+fn stairs(p: [f32; 2], scale: f32, up: bool) -> Path {
+    let (x, y) = point(p, scale);
+    let tip = if up { -0.3 } else { 0.3 } * scale;
+    Path::new()
+        .set("class", if up { "stairs stairs_up" } else { "stairs stairs_down" })
+        .set("d",
+            Data::new()
+            .move_to((x, y + tip))
+            .line_to((x + 0.3 * scale, y - tip))
+            .line_to((x - 0.3 * scale, y - tip))
+            .close()
+        )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn render_svg_floors(
+    space: &impl SpaceRenderer<3>,
+    edges: &[Edge],
+    nodes: &[Node],
+    layers: usize,
+    width: usize,
+    height: usize,
+    scale: f32,
+    start: usize,
+    end: usize,
+) -> Document {
+    let floors = Floors{
+        space,
+        stride: (width + 1) as f32,
+    };
+    let is_stairs = |eid: usize| {
+        let (start, end) = space.edge_position(eid);
+        start[0] != end[0]
+    };
+    let total_width = layers * (width + 1) - 1;
+    let mut doc = Document::new()
+        .set("viewBox", (-0.5 * scale, -0.5 * scale, (total_width+2) as f32 *scale, (height+2) as f32*scale))
+    ;
+    for node in render_nodes_2d(&floors, nodes, scale) {
+        doc = doc.add(node);
+    }
+    doc = doc.add(node(&floors, start, scale, "start"))
+        .add(node(&floors, end, scale, "end"));
+    for wall in walls_2d(&floors, edges.iter().enumerate().filter(|(eid, _e)| !is_stairs(*eid)), scale) {
+        doc = doc.add(wall);
+    }
+    for (eid, e) in edges.iter().enumerate().filter(|(eid, _e)| is_stairs(*eid)) {
+        if !matches!(e.direction, EdgeDirection::Forward | EdgeDirection::Backward | EdgeDirection::Open | EdgeDirection::Border) {
+            continue
+        }
+        let (lower, upper) = space.edge_position(eid);
+        let (lower, upper) = if lower[0] < upper[0] { (lower, upper) } else { (upper, lower) };
+        let (lower, upper) = (floors.flatten(lower), floors.flatten(upper));
+        doc = doc.add(stairs(lower, scale, true))
+            .add(stairs(upper, scale, false));
+        if e.solution {
+            for p in [lower, upper] {
+                let (cx, cy) = point(p, scale);
+                doc = doc.add(
+                    Circle::new()
+                        .set("class", "solution")
+                        .set("cy", cy)
+                        .set("cx", cx)
+                        .set("r", scale * 0.25)
+                );
+            }
+        }
+    }
+    for dot in solution_2d(&floors, edges.iter().enumerate().filter(|(eid, _e)| !is_stairs(*eid)), scale) {
+        doc = doc.add(dot);
+    }
+
+    for layer in 0..layers {
+        doc = doc.add(
             Rectangle::new()
                 .set("class", "wall")
-                .set("x", 0)
+                .set("x", (layer * (width + 1)) as f32 * scale)
                 .set("y", 0)
                 .set("width", width as f32*scale)
                 .set("height", height as f32*scale)
-        )
+        );
+    }
+
+    doc
 }
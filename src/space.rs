@@ -3,24 +3,33 @@ use crate::{
     node::Node,
     edge::{Edge,EdgeDirection},
     error::*,
+    generator::{Generator, Algorithm},
 };
 
+/// Picks `n` different enabled nodes out of the first `len`, or an error if
+/// there are fewer than `n` of them.
 #[inline]
-fn n_unique_indices(rng: &mut impl rand::Rng, len: usize, n: usize) -> impl Iterator<Item=usize> {
+pub fn n_unique_indices(space: &impl Space, rng: &mut impl rand::Rng, len: usize, n: usize) -> Result<impl Iterator<Item=usize>> {
+    if n > 0 && (0..len).filter(|nid| space.node_enabled(*nid)).nth(n - 1).is_none() {
+        return Err(Error::MessageError("fewer enabled nodes than asked for"))
+    }
     let mut root_idcs = std::collections::HashSet::<usize>::new();
     for _ in 0..n {
         loop {
             let choice = rng.random_range(0..len);
-            if !root_idcs.contains(&choice) {
+            if space.node_enabled(choice) && !root_idcs.contains(&choice) {
                 root_idcs.insert(choice);
                 break
             }
         }
     }
-    root_idcs.into_iter()
+    Ok(root_idcs.into_iter())
 }
+/// One step of the frontier flood: takes a random node off `visiting`,
+/// opens an edge to each of its unvisited neighbours, adding them to its
+/// group and to `visiting`, and closes the edges to visited ones.
 #[inline]
-fn visit(space: &impl Space, rng: &mut impl rand::Rng, nodes: &mut [Node], edges: &mut [Edge], visiting: &mut Vec<usize>) -> Result<()> {
+pub fn visit(space: &impl Space, rng: &mut impl rand::Rng, nodes: &mut [Node], edges: &mut [Edge], visiting: &mut Vec<usize>) -> Result<()> {
     let idx = visiting.swap_remove(rng.random_range(0..visiting.len()));
     let node_group = nodes[idx].group;
     node_nodes(space, idx, edges, |edge, nidx| {
@@ -29,11 +38,7 @@ fn visit(space: &impl Space, rng: &mut impl rand::Rng, nodes: &mut [Node], edges
             edge.direction = EdgeDirection::Closed;
             return
         }
-        if idx < nidx {
-            edge.direction = EdgeDirection::Forward
-        } else {
-            edge.direction = EdgeDirection::Backward
-        }
+        open_edge(edge, idx, nidx);
         visit_node.group = node_group;
         visiting.push(nidx);
     });
@@ -53,9 +58,21 @@ fn find_root(space: &impl Space, edges: &mut [Edge], node_id: usize) {
     }
 }
 
+/// Opens an edge in the maze's tree, pointing from `child` back to
+/// `parent`, which is the way [`Space::solve`] follows it to the root.
+#[inline]
+pub fn open_edge(edge: &mut Edge, parent: usize, child: usize) {
+    if parent < child {
+        edge.direction = EdgeDirection::Forward
+    } else {
+        edge.direction = EdgeDirection::Backward
+    }
+}
+
+/// The node at the other end of an edge.
 #[inline]
-fn follow_edge(space: &impl Space, node_id: usize, edge_id: usize) -> Option<usize> {
-    space.edge_nodes(edge_id).filter(|nid| *nid != node_id).next()
+pub fn follow_edge(space: &impl Space, node_id: usize, edge_id: usize) -> Option<usize> {
+    space.edge_nodes(edge_id).find(|nid| *nid != node_id)
 }
 #[inline]
 fn node_parent(space: &impl Space, node_id: usize, edges: &mut [Edge], mut on_match: impl FnMut(&mut Edge)) -> Option<usize> {
@@ -89,13 +106,99 @@ fn node_parent(space: &impl Space, node_id: usize, edges: &mut [Edge], mut on_ma
         }
     }
 }
+/// Calls `on_each` with every edge of a node that is still
+/// [`EdgeDirection::Unknown`], and the node at its other end.
 #[inline]
-fn node_nodes(space: &impl Space, node_id: usize, edges: &mut [Edge], mut on_each: impl FnMut(&mut Edge, usize)) { 
+pub fn node_nodes(space: &impl Space, node_id: usize, edges: &mut [Edge], mut on_each: impl FnMut(&mut Edge, usize)) { 
     space.node_edges(node_id).for_each(|eid| if edges[eid].direction == EdgeDirection::Unknown{
         on_each(&mut edges[eid], follow_edge(space, node_id, eid).unwrap())
     });
 }
 
+/// How many random pairs of nodes [`Space::choose_endpoints`] tries before
+/// giving up on a strategy that picks pairs at random.
+const ENDPOINT_TRIES: usize = 1000;
+
+/// How [`Space::choose_endpoints`] picks the start and end of a maze.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Endpoints {
+    /// Any two enabled nodes, like [`Space::get_endpoints`].
+    #[default]
+    Random,
+    /// The two ends of a longest shortest path through the maze.
+    Farthest,
+    /// Two nodes on the outer border, as far apart as the border allows,
+    /// for an entrance and exit cut into the outer wall.
+    Border,
+    /// Two nodes in different zones.
+    DifferentZones,
+    /// Two nodes at least this many steps apart.
+    MinDistance(usize),
+}
+
+/// How many steps each node is from `start` through open edges. `None`
+/// means it cannot be reached.
+fn distances(space: &impl Space, edges: &[Edge], start: usize) -> Vec<Option<usize>> {
+    let mut distance = vec![None; space.num_nodes()];
+    let mut visiting = std::collections::VecDeque::from([start]);
+    distance[start] = Some(0);
+    while let Some(nid) = visiting.pop_front() {
+        let next = distance[nid].map(|d| d + 1);
+        for eid in space.node_edges(nid).filter(|eid| edges[*eid].direction.is_open()) {
+            let other = follow_edge(space, nid, eid).unwrap();
+            if distance[other].is_none() {
+                distance[other] = next;
+                visiting.push_back(other);
+            }
+        }
+    }
+    distance
+}
+
+/// The node among `candidates` farthest from `start`.
+#[inline]
+fn farthest(space: &impl Space, edges: &[Edge], start: usize, candidates: &[usize]) -> Option<usize> {
+    let distance = distances(space, edges, start);
+    candidates.iter()
+        .filter_map(|nid| distance[*nid].map(|d| (d, *nid)))
+        .max()
+        .map(|(_d, nid)| nid)
+}
+
+/// A shortest path from `start` to `end` through the open edges that
+/// `passable` allows, as the edges in order from `start`, or `None` if
+/// there is none. Nothing is marked.
+pub fn search(space: &impl Space, edges: &[Edge], start: usize, end: usize, passable: impl Fn(usize) -> bool) -> Option<Vec<usize>> {
+    let mut came_by = vec![None; space.num_nodes()];
+    let mut seen = vec![false; space.num_nodes()];
+    let mut visiting = std::collections::VecDeque::from([start]);
+    seen[start] = true;
+    while let Some(nid) = visiting.pop_front() {
+        if nid == end {
+            break
+        }
+        for eid in space.node_edges(nid).filter(|eid| edges[*eid].direction.is_open() && passable(*eid)) {
+            let other = follow_edge(space, nid, eid).unwrap();
+            if !seen[other] {
+                seen[other] = true;
+                came_by[other] = Some((eid, nid));
+                visiting.push_back(other);
+            }
+        }
+    }
+    if !seen[end] {
+        return None
+    }
+    let mut path = Vec::new();
+    let mut nid = end;
+    while let Some((eid, from)) = came_by[nid] {
+        path.push(eid);
+        nid = from;
+    }
+    path.reverse();
+    Some(path)
+}
+
 pub struct SolutionIterator<'a, 'b, S: Space> {
     space: &'a S,
     edges: &'b [Edge],
@@ -107,13 +210,13 @@ impl<'a, 'b, S: Space> std::iter::Iterator for SolutionIterator<'a, 'b, S> {
     type Item = usize;
     fn next(&mut self) -> Option<Self::Item> {
         match self.space.node_edges(self.last_node)
-            .filter(|eid| {
+            .find(|eid| {
                 let edge = &self.edges[*eid];
                 edge.solution && match self.last_edge {
                     None => true,
                     Some(leid) => *eid != leid,
                 }
-            }).next() {
+            }) {
                 None => None,
                 Some(eid) => {
                     self.last_edge = Some(eid);
@@ -132,43 +235,135 @@ pub trait Space {
     fn edge_nodes(&self, edge_id: usize) -> impl Iterator<Item=usize>;
 
     // Provided Methods
+
+    /// Whether a node is part of the maze. Disabled nodes have no edges and
+    /// are never laid out or chosen as endpoints.
+    fn node_enabled(&self, _node_id: usize) -> bool {
+        true
+    }
+
+    /// Whether a node is at the edge of the space, beside its outer wall.
+    fn node_on_border(&self, _node_id: usize) -> bool {
+        false
+    }
+
     fn layout(&self, roots: usize, rng: &mut impl rand::Rng) -> Result<(Vec<Node>, Vec<Edge>)>
         where Self: Sized
+    {
+        self.layout_with(roots, Algorithm::Frontier, rng)
+    }
+
+    fn layout_with(&self, roots: usize, generator: impl Generator, rng: &mut impl rand::Rng) -> Result<(Vec<Node>, Vec<Edge>)>
+        where Self: Sized
+    {
+        let (nodes, mut edges) = generator.generate(self, roots, rng)?;
+        self.settle(&nodes, &mut edges);
+        Ok((nodes, edges))
+    }
+
+    /// Tidies up a maze a generator has just laid out, before
+    /// [`Space::layout_with`] returns it, for spaces with rules that a
+    /// generator knows nothing about. Most spaces need nothing.
+    fn settle(&self, _nodes: &[Node], _edges: &mut [Edge]) {}
+
+    /// Picks two different enabled nodes at random, or `None` if there
+    /// are fewer than two.
+    fn get_endpoints(&self, rng: &mut impl rand::Rng) -> Option<(usize, usize)>
+        where Self: Sized
     {
         let num_nodes = self.num_nodes();
-        let num_edges = self.num_edges();
-        let mut nodes = Vec::<Node>::with_capacity(num_nodes);
-        let mut visiting = Vec::<usize>::with_capacity(num_nodes);
-        for _ in 0..num_nodes {
-            nodes.push(Default::default());
-        }
-        let mut edges = Vec::<Edge>::with_capacity(num_edges);
-        for _ in 0..num_edges {
-            edges.push(Default::default())
+        (0..num_nodes).filter(|nid| self.node_enabled(*nid)).nth(1)?;
+        let mut start = rng.random_range(0..num_nodes);
+        while !self.node_enabled(start) {
+            start = rng.random_range(0..num_nodes);
         }
-        for (i, idx) in n_unique_indices(rng, num_nodes, roots).enumerate() {
-            nodes[idx].group = Some(i);
-            nodes[idx].root = true;
-            visiting.push(idx);
+        let mut end = start;
+        while end == start || !self.node_enabled(end) {
+            end = rng.random_range(0..num_nodes);
         }
-        loop {
-            if visiting.is_empty() {
-                return Ok((nodes, edges))
+        Some((start, end))
+    }
+
+    /// Picks a start and end for a laid-out maze with `strategy`, or `None`
+    /// if it cannot find a pair that fits. Zones are the groups of `nodes`.
+    fn choose_endpoints(&self, nodes: &[Node], edges: &[Edge], strategy: Endpoints, rng: &mut impl rand::Rng) -> Option<(usize, usize)>
+        where Self: Sized
+    {
+        match strategy {
+            Endpoints::Random => self.get_endpoints(rng),
+            Endpoints::Farthest | Endpoints::Border => {
+                let candidates = (0..self.num_nodes())
+                    .filter(|nid| self.node_enabled(*nid))
+                    .filter(|nid| strategy != Endpoints::Border || self.node_on_border(*nid))
+                    .collect::<Vec<_>>();
+                if candidates.len() < 2 {
+                    return None
+                }
+                // The node farthest from anywhere is one end of a longest
+                // path in a tree, and the node farthest from it the other.
+                let any = candidates[rng.random_range(0..candidates.len())];
+                let start = farthest(self, edges, any, &candidates)?;
+                let end = farthest(self, edges, start, &candidates)?;
+                (start != end).then_some((start, end))
             }
-            visit(self, rng, &mut nodes, &mut edges, &mut visiting)?;
+            Endpoints::DifferentZones => (0..ENDPOINT_TRIES)
+                .map_while(|_| self.get_endpoints(rng))
+                .find(|(start, end)| nodes[*start].group.is_some()
+                    && nodes[*end].group.is_some()
+                    && nodes[*start].group != nodes[*end].group),
+            Endpoints::MinDistance(min) => (0..ENDPOINT_TRIES)
+                .map_while(|_| self.get_endpoints(rng))
+                .find(|(start, end)| distances(self, edges, *start)[*end].is_some_and(|d| d >= min)),
         }
     }
 
-    fn get_endpoints(&self, rng: &mut impl rand::Rng) -> (usize, usize)
+    /// Opens walls at a `fraction` of the dead ends, chosen at random, so
+    /// the maze has loops. Each wall opened is to a neighbour in the same
+    /// zone, another dead end if there is one, and is left
+    /// [`EdgeDirection::Open`] so that [`Space::solve`] still follows the
+    /// tree. A dead end already opened by another is skipped.
+    fn braid(&self, nodes: &[Node], edges: &mut [Edge], fraction: f64, rng: &mut impl rand::Rng)
         where Self: Sized
     {
-        let num_nodes = self.num_nodes();
-        let start = rng.random_range(0..num_nodes);
-        let mut end = start;
-        while end == start {
-            end = rng.random_range(0..num_nodes);
+        use rand::seq::SliceRandom;
+        let exits = |edges: &[Edge], nid: usize| self.node_edges(nid).filter(|eid| edges[*eid].direction.is_open()).count();
+        let mut dead_ends = (0..self.num_nodes())
+            .filter(|nid| nodes[*nid].group.is_some() && exits(edges, *nid) == 1)
+            .collect::<Vec<_>>();
+        dead_ends.shuffle(rng);
+        let count = (fraction.clamp(0.0, 1.0) * dead_ends.len() as f64).round() as usize;
+        for nid in dead_ends.into_iter().take(count) {
+            if exits(edges, nid) != 1 {
+                continue
+            }
+            let walls = self.node_edges(nid)
+                .filter(|eid| edges[*eid].direction == EdgeDirection::Closed)
+                .map(|eid| (eid, follow_edge(self, nid, eid).unwrap()))
+                .filter(|(_eid, other)| nodes[*other].group == nodes[nid].group)
+                .collect::<Vec<_>>();
+            let ends = walls.iter()
+                .filter(|(_eid, other)| exits(edges, *other) == 1)
+                .copied()
+                .collect::<Vec<_>>();
+            let choices = if ends.is_empty() { walls } else { ends };
+            if choices.is_empty() {
+                continue
+            }
+            let (eid, _other) = choices[rng.random_range(0..choices.len())];
+            edges[eid].direction = EdgeDirection::Open;
         }
-        (start, end)
+    }
+
+    /// Finds a shortest path by searching every open edge, so it works
+    /// when the maze has loops and is no longer a tree. Marks the edges on
+    /// the path as the solution and returns them in order from `start`, or
+    /// `None` if `end` cannot be reached.
+    fn solve_search(&self, edges: &mut [Edge], start: usize, end: usize) -> Option<Vec<usize>>
+        where Self: Sized
+    {
+        let path = search(self, edges, start, end, |_eid| true)?;
+        path.iter().for_each(|eid| edges[*eid].solution = true);
+        Some(path)
     }
 
     fn solve(&self, edges: &mut [Edge], start: usize, end: usize) -> impl Iterator<Item=usize>
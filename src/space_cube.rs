@@ -0,0 +1,192 @@
+use std::iter::Iterator;
+use super::space::Space;
+use super::space_square::SpaceSquare;
+use super::render::SpaceRenderer;
+
+macro_rules! rq {
+    ( ( $lhs:expr ) / ( $rhs:expr )) => {
+        (
+            ($lhs as usize / $rhs as usize),
+            ($lhs as usize % $rhs as usize)
+        )
+    }
+}
+
+/// A stack of square floors, where each cell also connects to the cells
+/// directly above and below it.
+///
+/// Each layer holds the edges of its own floor, numbered as in
+/// [`SpaceSquare`], followed by the edges up to the next layer, one per
+/// cell.
+#[derive(Debug)]
+pub struct SpaceCube {
+    layers: usize,
+    rows: usize,
+    cols: usize,
+    floor: SpaceSquare,
+}
+
+impl SpaceCube {
+    pub fn new(layers: usize, rows: usize, cols: usize) -> Self {
+        Self{
+            layers,
+            rows,
+            cols,
+            floor: SpaceSquare::new(rows, cols),
+        }
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    #[inline]
+    fn floor_nodes(&self) -> usize {
+        self.rows * self.cols
+    }
+
+    #[inline]
+    fn floor_edges(&self) -> usize {
+        self.floor.num_edges()
+    }
+
+    #[inline]
+    fn edge_layer_len(&self) -> usize {
+        self.floor_edges() + self.floor_nodes()
+    }
+
+    #[inline]
+    fn up_edge(&self, layer: usize, floor_node: usize) -> usize {
+        layer * self.edge_layer_len() + self.floor_edges() + floor_node
+    }
+}
+
+impl Space for SpaceCube {
+    fn num_nodes(&self) -> usize {
+        self.layers * self.floor_nodes()
+    }
+    fn num_edges(&self) -> usize {
+        if self.num_nodes() == 0 {
+            return 0
+        }
+        self.layers * self.edge_layer_len() - self.floor_nodes()
+    }
+    fn node_edges(&self, node_id: usize) -> impl Iterator<Item=usize> {
+        let (layer, floor_node) = rq!((node_id) / (self.floor_nodes()));
+        let base = layer * self.edge_layer_len();
+        let down = (layer > 0).then(|| self.up_edge(layer - 1, floor_node));
+        let up = (layer + 1 < self.layers).then(|| self.up_edge(layer, floor_node));
+        self.floor.node_edges(floor_node)
+            .map(move |eid| base + eid)
+            .chain(down)
+            .chain(up)
+    }
+    fn edge_nodes(&self, edge_id: usize) -> impl Iterator<Item=usize> {
+        let (layer, idx) = rq!((edge_id) / (self.edge_layer_len()));
+        let base = layer * self.floor_nodes();
+        let ends = if idx < self.floor_edges() {
+            let mut ends = self.floor.edge_nodes(idx);
+            [base + ends.next().unwrap(), base + ends.next().unwrap()]
+        } else {
+            let floor_node = idx - self.floor_edges();
+            [base + floor_node, base + self.floor_nodes() + floor_node]
+        };
+        ends.into_iter()
+    }
+}
+
+impl SpaceRenderer<3> for SpaceCube {
+    fn edge_position(&self, edge_id: usize) -> ([f32; 3], [f32; 3]) {
+        let mut end_nodes = self.edge_nodes(edge_id);
+        let start = end_nodes.next().unwrap();
+        let end = end_nodes.next().unwrap();
+        (self.node_position(start), self.node_position(end))
+    }
+    fn node_position(&self, node_id: usize) -> [f32; 3] {
+        let (layer, floor_node) = rq!((node_id) / (self.floor_nodes()));
+        let (row, col) = rq!((floor_node) / (self.cols));
+        [ layer as f32, row as f32, col as f32 ]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::space::Space;
+    use super::*;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_num_edges() {
+        init_tests();
+        let space = SpaceCube::new(2, 3, 3);
+        assert_eq!(space.num_edges(), 12 * 2 + 9);
+        let count = (0..space.num_nodes())
+            .map(|nid| space.node_edges(nid).count())
+            .sum::<usize>();
+        assert_eq!(space.num_edges() * 2, count);
+    }
+
+    #[test]
+    fn test_node_edges() {
+        init_tests();
+        let space = SpaceCube::new(3, 3, 3);
+        macro_rules! test {
+            ($idx:literal => $edges:expr) => {
+                {
+                    let mut edges = space.node_edges($idx).collect::<Vec<_>>();
+                    edges.sort();
+                    assert_eq!(edges, $edges);
+                }
+            }
+        }
+        test!(0 => vec![0, 2, 12]);
+        test!(13 => vec![16, 24, 26, 27, 29, 37]);
+        test!(26 => vec![41, 51, 53]);
+    }
+
+    #[test]
+    fn test_edge_position() {
+        init_tests();
+        let space = SpaceCube::new(3, 3, 4);
+        for eid in 0..space.num_edges() {
+            let (start, end) = space.edge_position(eid);
+            let moved = start.iter().zip(end.iter()).filter(|(a, b)| a != b).count();
+            assert_eq!(moved, 1, "edge {eid} is diagonal: {start:?} {end:?}");
+        }
+    }
+
+    #[test]
+    fn test_neighbours() {
+        init_tests();
+        let space = SpaceCube::new(3, 4, 5);
+        let size = [3, 4, 5];
+        for nid in 0..space.num_nodes() {
+            let at = space.node_position(nid);
+            let neighbours = space.node_edges(nid)
+                .map(|eid| {
+                    let ends = space.edge_nodes(eid).collect::<Vec<_>>();
+                    assert!(ends.contains(&nid), "edge {eid} does not touch node {nid}");
+                    space.node_position(*ends.iter().find(|other| **other != nid).unwrap())
+                })
+                .collect::<Vec<_>>();
+            // One step along one axis, with nothing wrapping round the
+            // sides, top or bottom.
+            assert!(neighbours.iter().all(|other| {
+                other.iter().zip(at.iter()).map(|(a, b)| (a - b).abs()).sum::<f32>() == 1.0
+            }), "node {nid}");
+            let sides = at.iter().zip(size.iter())
+                .map(|(pos, len)| (*pos == 0.0) as usize + (*pos == (len - 1) as f32) as usize)
+                .sum::<usize>();
+            assert_eq!(neighbours.len(), 6 - sides, "node {nid}");
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::iter::Iterator;
+use std::f32::consts::TAU;
+use super::space::Space;
+use super::render::SpaceRenderer;
+use super::error::*;
+
+/// How long the mark across a closed edge is, in cell units.
+const WALL_LENGTH: f32 = 0.5;
+
+/// An arbitrary graph given as a list of edges between numbered nodes, such
+/// as a floor plan, a subway map or a hand-drawn graph.
+///
+/// Nodes may be given positions for rendering, as `[y, x]` in cell units
+/// like every other space. Without them, the nodes are spaced evenly around
+/// a circle.
+#[derive(Debug)]
+pub struct SpaceGraph {
+    edges: Vec<(usize, usize)>,
+    adjacency: Vec<Vec<usize>>,
+    positions: Option<Vec<[f32; 2]>>,
+}
+
+impl SpaceGraph {
+    pub fn new(num_nodes: usize, edges: Vec<(usize, usize)>) -> Result<Self> {
+        let mut adjacency = vec![Vec::new(); num_nodes];
+        for (eid, (a, b)) in edges.iter().enumerate() {
+            for nid in [*a, *b] {
+                if nid >= num_nodes {
+                    return Err(Error::NodeOutOfBounds(nid, num_nodes))
+                }
+            }
+            if a == b {
+                return Err(Error::MessageError("an edge cannot join a node to itself"))
+            }
+            adjacency[*a].push(eid);
+            adjacency[*b].push(eid);
+        }
+        Ok(Self{
+            edges,
+            adjacency,
+            positions: None,
+        })
+    }
+
+    pub fn with_positions(mut self, positions: Vec<[f32; 2]>) -> Result<Self> {
+        if positions.len() != self.adjacency.len() {
+            return Err(Error::MessageError("there must be one position for each node"))
+        }
+        self.positions = Some(positions);
+        Ok(self)
+    }
+
+    /// The width and height of the graph in cell units, rounded up, for
+    /// sizing a document.
+    pub fn extent(&self) -> (usize, usize) {
+        let (height, width) = (0..self.num_nodes())
+            .map(|nid| self.node_position(nid))
+            .fold((0.0f32, 0.0f32), |(h, w), p| (h.max(p[0]), w.max(p[1])));
+        (width.ceil() as usize + 1, height.ceil() as usize + 1)
+    }
+}
+
+impl Space for SpaceGraph {
+    fn num_nodes(&self) -> usize {
+        self.adjacency.len()
+    }
+    fn num_edges(&self) -> usize {
+        self.edges.len()
+    }
+    fn node_edges(&self, node_id: usize) -> impl Iterator<Item=usize> {
+        self.adjacency[node_id].iter().copied()
+    }
+    fn edge_nodes(&self, edge_id: usize) -> impl Iterator<Item=usize> {
+        let (a, b) = self.edges[edge_id];
+        [a, b].into_iter()
+    }
+}
+
+impl SpaceRenderer<2> for SpaceGraph {
+    fn edge_position(&self, edge_id: usize) -> ([f32; 2], [f32; 2]) {
+        let (a, b) = self.edges[edge_id];
+        (self.node_position(a), self.node_position(b))
+    }
+    fn node_position(&self, node_id: usize) -> [f32; 2] {
+        match &self.positions {
+            Some(positions) => positions[node_id],
+            None => {
+                let radius = (self.num_nodes() as f32 / TAU).max(1.0);
+                let (sin, cos) = (TAU * node_id as f32 / self.num_nodes() as f32).sin_cos();
+                [radius * (1.0 + sin), radius * (1.0 + cos)]
+            }
+        }
+    }
+    /// Edges can be any length, so a closed edge is marked by a short wall
+    /// across its middle.
+    fn wall_position(&self, edge_id: usize) -> Option<([f32; 2], [f32; 2])> {
+        let (start, end) = self.edge_position(edge_id);
+        let (dy, dx) = (end[0] - start[0], end[1] - start[1]);
+        let length = (dy * dy + dx * dx).sqrt().max(f32::EPSILON);
+        let (ny, nx) = (dx / length * WALL_LENGTH * 0.5, -dy / length * WALL_LENGTH * 0.5);
+        let mid = [(start[0] + end[0]) / 2.0, (start[1] + end[1]) / 2.0];
+        Some(([mid[0] - ny, mid[1] - nx], [mid[0] + ny, mid[1] + nx]))
+    }
+    fn border_walls(&self) -> Option<Vec<([f32; 2], [f32; 2])>> {
+        Some(Vec::new())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::space::Space;
+    use super::*;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_new() {
+        init_tests();
+        assert!(matches!(SpaceGraph::new(2, vec![(0, 2)]), Err(Error::NodeOutOfBounds(2, 2))));
+        assert!(SpaceGraph::new(2, vec![(1, 1)]).is_err());
+        let space = SpaceGraph::new(3, vec![(0, 1), (1, 2)]).unwrap();
+        assert!(space.with_positions(vec![[0.0, 0.0]]).is_err());
+    }
+
+    #[test]
+    fn test_node_edges() {
+        init_tests();
+        let space = SpaceGraph::new(4, vec![(0, 1), (1, 2), (2, 0), (2, 3)]).unwrap();
+        assert_eq!(space.num_edges(), 4);
+        assert_eq!(space.node_edges(0).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(space.node_edges(2).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(space.node_edges(3).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(space.edge_nodes(3).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_wheel() {
+        init_tests();
+        // A wheel: a ring of eight nodes around a hub.
+        let mut pairs = (1..=8).map(|n| (0, n)).collect::<Vec<_>>();
+        pairs.extend((1..=8).map(|n| (n, n % 8 + 1)));
+        let space = SpaceGraph::new(9, pairs).unwrap();
+        assert_eq!(space.node_edges(0).count(), 8);
+        for nid in 1..=8 {
+            let mut neighbours = space.node_edges(nid)
+                .map(|eid| space.edge_nodes(eid).find(|other| *other != nid).unwrap())
+                .collect::<Vec<_>>();
+            neighbours.sort();
+            let mut expected = vec![0, nid % 8 + 1, (nid + 6) % 8 + 1];
+            expected.sort();
+            assert_eq!(neighbours, expected, "node {nid}");
+        }
+        for eid in 0..space.num_edges() {
+            assert!(space.edge_nodes(eid).all(|nid| space.node_edges(nid).any(|other| other == eid)), "edge {eid}");
+        }
+
+        // Without positions, the nodes are spaced evenly round a circle.
+        let distance = |a: [f32; 2], b: [f32; 2]| ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+        let steps = (0..9)
+            .map(|nid| distance(space.node_position(nid), space.node_position((nid + 1) % 9)))
+            .collect::<Vec<_>>();
+        assert!(steps.iter().all(|step| (step - steps[0]).abs() < 1e-4), "{steps:?}");
+        let (width, height) = space.extent();
+        assert_eq!(width, height);
+    }
+}
@@ -0,0 +1,375 @@
+use std::iter::Iterator;
+use super::space::Space;
+use super::render::SpaceRenderer;
+
+macro_rules! rq {
+    ( ( $lhs:expr ) / ( $rhs:expr )) => {
+        (
+            ($lhs as usize / $rhs as usize),
+            ($lhs as usize % $rhs as usize)
+        )
+    }
+}
+
+const ROW_HEIGHT: f32 = 0.866_025_4; // sqrt(3) / 2
+const SIDE: f32 = 0.577_350_3; // 1 / sqrt(3)
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HexOrientation {
+    /// Cells have a corner at the top and rows are staggered.
+    #[default]
+    PointyTop,
+    /// Cells have a side at the top and columns are staggered.
+    FlatTop,
+}
+
+/// A grid of hexagonal cells, each of which has up to six neighbours.
+///
+/// Odd rows (pointy-top) or odd columns (flat-top) are pushed half a cell
+/// to the right or down. Internally, a flat-top grid is a pointy-top grid
+/// on its side: rows become "majors" and columns become "minors".
+#[derive(Debug)]
+pub struct SpaceHex {
+    rows: usize,
+    cols: usize,
+    orientation: HexOrientation,
+}
+
+// Directions, clockwise from East, in the pointy-top lattice.
+const EAST: usize = 0;
+const SOUTH_EAST: usize = 1;
+const SOUTH_WEST: usize = 2;
+const WEST: usize = 3;
+const NORTH_WEST: usize = 4;
+const NORTH_EAST: usize = 5;
+
+impl SpaceHex {
+    pub fn new(rows: usize, cols: usize, orientation: HexOrientation) -> Self {
+        Self{
+            rows,
+            cols,
+            orientation,
+        }
+    }
+
+    /// The width and height of the grid in cell units, rounded up, for
+    /// sizing a document.
+    pub fn extent(&self) -> (usize, usize) {
+        let (width, height) = match self.orientation {
+            HexOrientation::PointyTop => (
+                self.cols as f32 + 0.5,
+                (self.rows as f32 - 1.0) * ROW_HEIGHT + 2.0 * SIDE,
+            ),
+            HexOrientation::FlatTop => (
+                (self.cols as f32 - 1.0) * ROW_HEIGHT + 2.0 * SIDE,
+                self.rows as f32 + 0.5,
+            ),
+        };
+        (width.ceil() as usize, height.ceil() as usize)
+    }
+
+    #[inline]
+    fn majors(&self) -> usize {
+        match self.orientation {
+            HexOrientation::PointyTop => self.rows,
+            HexOrientation::FlatTop => self.cols,
+        }
+    }
+
+    #[inline]
+    fn minors(&self) -> usize {
+        match self.orientation {
+            HexOrientation::PointyTop => self.cols,
+            HexOrientation::FlatTop => self.rows,
+        }
+    }
+
+    #[inline]
+    fn node_lattice(&self, node_id: usize) -> (usize, usize) {
+        let (row, col) = rq!((node_id) / (self.cols));
+        match self.orientation {
+            HexOrientation::PointyTop => (row, col),
+            HexOrientation::FlatTop => (col, row),
+        }
+    }
+
+    #[inline]
+    fn lattice_node(&self, major: usize, minor: usize) -> usize {
+        match self.orientation {
+            HexOrientation::PointyTop => major * self.cols + minor,
+            HexOrientation::FlatTop => minor * self.cols + major,
+        }
+    }
+
+    /// Each major holds its own minor-wise edges followed by the edges
+    /// down to the next major, ordered by their position along it.
+    #[inline]
+    fn edge_major_len(&self) -> usize {
+        self.minors() * 3 - 2
+    }
+
+    #[inline]
+    fn down_edge(&self, major: usize, k: usize) -> usize {
+        major * self.edge_major_len() + self.minors() - 1 + k
+    }
+
+    /// The neighbouring node and connecting edge in each direction, if any.
+    fn neighbours(&self, node_id: usize) -> [Option<(usize, usize)>; 6] {
+        let (major, minor) = self.node_lattice(node_id);
+        let (majors, minors) = (self.majors(), self.minors());
+        let odd = major % 2 == 1;
+        let mut out = [None; 6];
+        if minor + 1 < minors {
+            out[EAST] = Some((
+                self.lattice_node(major, minor + 1),
+                major * self.edge_major_len() + minor,
+            ));
+        }
+        if minor > 0 {
+            out[WEST] = Some((
+                self.lattice_node(major, minor - 1),
+                major * self.edge_major_len() + minor - 1,
+            ));
+        }
+        if major + 1 < majors {
+            if odd {
+                out[SOUTH_WEST] = Some((self.lattice_node(major + 1, minor), self.down_edge(major, 2 * minor)));
+                if minor + 1 < minors {
+                    out[SOUTH_EAST] = Some((self.lattice_node(major + 1, minor + 1), self.down_edge(major, 2 * minor + 1)));
+                }
+            } else {
+                if minor > 0 {
+                    out[SOUTH_WEST] = Some((self.lattice_node(major + 1, minor - 1), self.down_edge(major, 2 * minor - 1)));
+                }
+                out[SOUTH_EAST] = Some((self.lattice_node(major + 1, minor), self.down_edge(major, 2 * minor)));
+            }
+        }
+        if major > 0 {
+            if odd {
+                out[NORTH_WEST] = Some((self.lattice_node(major - 1, minor), self.down_edge(major - 1, 2 * minor)));
+                if minor + 1 < minors {
+                    out[NORTH_EAST] = Some((self.lattice_node(major - 1, minor + 1), self.down_edge(major - 1, 2 * minor + 1)));
+                }
+            } else {
+                if minor > 0 {
+                    out[NORTH_WEST] = Some((self.lattice_node(major - 1, minor - 1), self.down_edge(major - 1, 2 * minor - 1)));
+                }
+                out[NORTH_EAST] = Some((self.lattice_node(major - 1, minor), self.down_edge(major - 1, 2 * minor)));
+            }
+        }
+        out
+    }
+
+    /// Positions are worked out in the pointy-top lattice as `[y, x]` and
+    /// then turned on their side for flat-top grids.
+    #[inline]
+    fn orient(&self, p: [f32; 2]) -> [f32; 2] {
+        match self.orientation {
+            HexOrientation::PointyTop => p,
+            HexOrientation::FlatTop => [p[1], p[0]],
+        }
+    }
+
+    #[inline]
+    fn lattice_position(&self, node_id: usize) -> [f32; 2] {
+        let (major, minor) = self.node_lattice(node_id);
+        [
+            major as f32 * ROW_HEIGHT,
+            minor as f32 + 0.5 * (major % 2) as f32,
+        ]
+    }
+
+    /// The corner of a cell at the given angle, in degrees clockwise from
+    /// East, in the pointy-top lattice.
+    #[inline]
+    fn lattice_corner(&self, node_id: usize, degrees: f32) -> [f32; 2] {
+        let [y, x] = self.lattice_position(node_id);
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        [y + SIDE * sin, x + SIDE * cos]
+    }
+
+    /// The side of a cell facing the given direction.
+    #[inline]
+    fn side(&self, node_id: usize, direction: usize) -> ([f32; 2], [f32; 2]) {
+        let facing = 60.0 * direction as f32;
+        (
+            self.orient(self.lattice_corner(node_id, facing - 30.0)),
+            self.orient(self.lattice_corner(node_id, facing + 30.0)),
+        )
+    }
+}
+
+impl Space for SpaceHex {
+    fn num_nodes(&self) -> usize {
+        self.rows * self.cols
+    }
+    fn num_edges(&self) -> usize {
+        if self.num_nodes() == 0 {
+            return 0
+        }
+        self.majors() * self.edge_major_len() - (self.minors() * 2 - 1)
+    }
+    fn node_edges(&self, node_id: usize) -> impl Iterator<Item=usize> {
+        self.neighbours(node_id).into_iter().flatten().map(|(_nid, eid)| eid)
+    }
+    fn edge_nodes(&self, edge_id: usize) -> impl Iterator<Item=usize> {
+        let (major, idx) = rq!((edge_id) / (self.edge_major_len()));
+        let minors = self.minors();
+        let (a, b) = if idx < minors - 1 {
+            ((major, idx), (major, idx + 1))
+        } else {
+            let k = idx - (minors - 1);
+            let minor = k / 2;
+            match (major % 2 == 1, k % 2 == 1) {
+                (false, false) => ((major, minor), (major + 1, minor)),
+                (false, true) => ((major, minor + 1), (major + 1, minor)),
+                (true, false) => ((major, minor), (major + 1, minor)),
+                (true, true) => ((major, minor), (major + 1, minor + 1)),
+            }
+        };
+        [a, b].into_iter().map(|(major, minor)| self.lattice_node(major, minor))
+    }
+}
+
+impl SpaceRenderer<2> for SpaceHex {
+    fn edge_position(&self, edge_id: usize) -> ([f32; 2], [f32; 2]) {
+        let mut end_nodes = self.edge_nodes(edge_id);
+        let start = end_nodes.next().unwrap();
+        let end = end_nodes.next().unwrap();
+        (self.node_position(start), self.node_position(end))
+    }
+    fn node_position(&self, node_id: usize) -> [f32; 2] {
+        self.orient(self.lattice_position(node_id))
+    }
+    fn wall_position(&self, edge_id: usize) -> Option<([f32; 2], [f32; 2])> {
+        let start = self.edge_nodes(edge_id).next().unwrap();
+        self.neighbours(start).into_iter()
+            .position(|n| matches!(n, Some((_nid, eid)) if eid == edge_id))
+            .map(|direction| self.side(start, direction))
+    }
+    fn node_outline(&self, node_id: usize) -> Option<Vec<[f32; 2]>> {
+        Some(
+            (0..6)
+                .map(|k| self.orient(self.lattice_corner(node_id, 60.0 * k as f32 - 30.0)))
+                .collect()
+        )
+    }
+    fn border_walls(&self) -> Option<Vec<([f32; 2], [f32; 2])>> {
+        Some(
+            (0..self.num_nodes())
+                .flat_map(|nid| {
+                    self.neighbours(nid).into_iter()
+                        .enumerate()
+                        .filter(|(_direction, n)| n.is_none())
+                        .map(move |(direction, _n)| self.side(nid, direction))
+                })
+                .collect()
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::space::Space;
+    use super::*;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_num_edges() {
+        init_tests();
+        for orientation in [HexOrientation::PointyTop, HexOrientation::FlatTop] {
+            let space = SpaceHex::new(3, 4, orientation);
+            let count = (0..space.num_nodes())
+                .map(|nid| space.node_edges(nid).count())
+                .sum::<usize>();
+            assert_eq!(space.num_edges() * 2, count, "{orientation:?}");
+        }
+        assert_eq!(SpaceHex::new(3, 3, HexOrientation::PointyTop).num_edges(), 16);
+        assert_eq!(SpaceHex::new(1, 4, HexOrientation::PointyTop).num_edges(), 3);
+    }
+
+    #[test]
+    fn test_node_edges() {
+        init_tests();
+        let space = SpaceHex::new(3, 3, HexOrientation::PointyTop);
+        macro_rules! test {
+            ($idx:literal => $edges:expr) => {
+                {
+                    let mut edges = space.node_edges($idx).collect::<Vec<_>>();
+                    edges.sort();
+                    assert_eq!(edges, $edges);
+                }
+            }
+        }
+        test!(0 => vec![0, 2]);
+        test!(1 => vec![0, 1, 3, 4]);
+        test!(3 => vec![2, 3, 7, 9, 10]);
+        test!(4 => vec![4, 5, 7, 8, 11, 12]);
+        test!(5 => vec![6, 8, 13]);
+        test!(6 => vec![9, 14]);
+    }
+
+    #[test]
+    fn test_edge_nodes() {
+        init_tests();
+        for orientation in [HexOrientation::PointyTop, HexOrientation::FlatTop] {
+            let space = SpaceHex::new(4, 5, orientation);
+            for nid in 0..space.num_nodes() {
+                for eid in space.node_edges(nid) {
+                    let nodes = space.edge_nodes(eid).collect::<Vec<_>>();
+                    assert_eq!(nodes.len(), 2);
+                    assert!(nodes.contains(&nid), "{orientation:?}: edge {eid} does not touch node {nid}: {nodes:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_edge_position() {
+        init_tests();
+        for orientation in [HexOrientation::PointyTop, HexOrientation::FlatTop] {
+            let space = SpaceHex::new(4, 5, orientation);
+            for eid in 0..space.num_edges() {
+                let (start, end) = space.edge_position(eid);
+                let length = ((start[0] - end[0]).powi(2) + (start[1] - end[1]).powi(2)).sqrt();
+                assert!((length - 1.0).abs() < 1e-4, "{orientation:?}: edge {eid} has length {length}");
+                let (a, b) = space.wall_position(eid).unwrap();
+                let length = ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+                assert!((length - SIDE).abs() < 1e-4, "{orientation:?}: wall {eid} has length {length}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_neighbours() {
+        init_tests();
+        for orientation in [HexOrientation::PointyTop, HexOrientation::FlatTop] {
+            let space = SpaceHex::new(5, 6, orientation);
+            let near = |a: [f32; 2], b: [f32; 2]| (((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt() - 1.0).abs() < 1e-4;
+            for nid in 0..space.num_nodes() {
+                // Every cell whose centre is one step away shares an edge,
+                // and nothing wraps round the sides.
+                let at = space.node_position(nid);
+                let mut expected = (0..space.num_nodes())
+                    .filter(|other| near(at, space.node_position(*other)))
+                    .collect::<Vec<_>>();
+                let mut neighbours = space.node_edges(nid)
+                    .map(|eid| space.edge_nodes(eid).find(|other| *other != nid).unwrap())
+                    .collect::<Vec<_>>();
+                expected.sort();
+                neighbours.sort();
+                assert_eq!(neighbours, expected, "{orientation:?}: node {nid}");
+                assert!(neighbours.len() <= 6);
+            }
+        }
+    }
+}
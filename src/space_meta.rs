@@ -2,6 +2,7 @@ use crate::{
     edge::*,
     node::*,
     space::*,
+    error::*,
 };
 
 #[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Copy, Debug, Hash)]
@@ -15,7 +16,7 @@ impl BorderKey {
             Self(b, a)
         }
     }
-    fn into_iter(&self) -> impl Iterator<Item=usize> {
+    fn iter(&self) -> impl Iterator<Item=usize> {
         std::iter::from_fn({
             let mut count = 0;
             move || {
@@ -38,7 +39,7 @@ impl BorderKey {
 struct Border {
     key: BorderKey,
     edges: Vec<usize>,
-    gateway: Option<usize>,
+    gateways: Vec<usize>,
 }
 
 impl Border {
@@ -46,7 +47,7 @@ impl Border {
         Self{
             key,
             edges: vec![edge_id],
-            gateway: None,
+            gateways: Vec::new(),
         }
     }
     fn matches_group(&self, node_group: usize) -> bool {
@@ -59,14 +60,21 @@ impl Border {
         self.edges.push(edge_id)
     }
     pub fn choose_gateway(&mut self, rng: &mut impl rand::Rng) -> usize {
-        match self.gateway {
-            Some(gw) => gw,
-            None => {
+        self.choose_gateways(1, rng)[0]
+    }
+    /// Picks up to `count` different edges of the border as its gateways,
+    /// the first time it is asked, and the same ones every time after.
+    pub fn choose_gateways(&mut self, count: usize, rng: &mut impl rand::Rng) -> &[usize] {
+        if self.gateways.is_empty() {
+            let count = count.clamp(1, self.edges.len());
+            while self.gateways.len() < count {
                 let gw = self.edges[rng.random_range(0..self.edges.len())];
-                self.gateway = Some(gw);
-                gw
+                if !self.gateways.contains(&gw) {
+                    self.gateways.push(gw);
+                }
             }
         }
+        &self.gateways
     }
 }
 
@@ -81,11 +89,20 @@ impl SpaceMeta {
             node_groups: Default::default(),
             borders: Default::default(),
         };
-        for eid in 0..edges.len() {
+        for (eid, edge) in edges.iter().enumerate() {
+            // Edges the layout never reached are not part of the space.
+            if edge.direction == EdgeDirection::Unknown {
+                continue
+            }
             let mut node_groups = space.edge_nodes(eid)
-                .map(|nid| nodes[nid].group.unwrap())
+                .map(|nid| nodes[nid].group)
                 .take(2);
-            let (a, b) = (node_groups.next().unwrap(), node_groups.next().unwrap());
+            // Edges to nodes that were never laid out, such as masked ones,
+            // are not part of any border.
+            let (a, b) = match (node_groups.next().unwrap(), node_groups.next().unwrap()) {
+                (Some(a), Some(b)) => (a, b),
+                _ => continue,
+            };
             if a == b {
                 continue
             }
@@ -101,6 +118,12 @@ impl SpaceMeta {
                 this.node_groups.push(bk.1);
             }
         }
+        // A zone with no borders, such as the only one, is still a zone.
+        for group in nodes.iter().filter_map(|node| node.group) {
+            if !this.node_groups.contains(&group) {
+                this.node_groups.push(group);
+            }
+        }
 
         this
     }
@@ -111,11 +134,40 @@ impl SpaceMeta {
             edges[gw].direction = EdgeDirection::Border;
         }
     }
+    /// Like [`SpaceMeta::open_gateways`], but first opens a fraction of the
+    /// borders that the meta-layout closed between zones of the same group,
+    /// leaving them [`EdgeDirection::Open`], then opens up to
+    /// `gateways.per_border` gateways through every open border.
+    pub fn open_gateways_with(&mut self, meta_nodes: &[Node], meta_edges: &mut [Edge], edges: &mut [Edge], gateways: &Gateways, rng: &mut impl rand::Rng) {
+        if gateways.extra_borders > 0.0 {
+            use rand::seq::SliceRandom;
+            let mut closed = (0..meta_edges.len())
+                .filter(|meid| meta_edges[*meid].direction == EdgeDirection::Closed)
+                .filter(|meid| {
+                    let mut groups = self.edge_nodes(*meid).map(|nid| meta_nodes[nid].group);
+                    groups.next() == groups.next()
+                })
+                .collect::<Vec<_>>();
+            closed.shuffle(rng);
+            let count = (gateways.extra_borders.clamp(0.0, 1.0) * closed.len() as f64).round() as usize;
+            for meid in closed.into_iter().take(count) {
+                meta_edges[meid].direction = EdgeDirection::Open;
+            }
+        }
+        for (meid, _me) in meta_edges.iter().enumerate().filter(|(_, e)| e.direction != EdgeDirection::Closed) {
+            for gw in self.borders[meid].choose_gateways(gateways.per_border, rng) {
+                edges[*gw].direction = EdgeDirection::Border;
+            }
+        }
+    }
     pub fn gateway(&self, border_id: usize) -> Option<usize> {
-        if border_id < self.borders.len() {
-            self.borders[border_id].gateway
-        } else {
-            None
+        self.gateways(border_id).first().copied()
+    }
+    /// Every gateway opened through a border.
+    pub fn gateways(&self, border_id: usize) -> &[usize] {
+        match self.borders.get(border_id) {
+            Some(border) => &border.gateways,
+            None => &[],
         }
     }
     pub fn zone_index(&self, group_id: usize) -> Option<usize> {
@@ -146,8 +198,508 @@ impl Space for SpaceMeta {
     }
 
     fn edge_nodes(&self, edge_id: usize) -> impl Iterator<Item=usize> {
-        self.borders[edge_id].key.into_iter().map(|gid| {
+        self.borders[edge_id].key.iter().map(|gid| {
             self.node_groups.iter().position(|z| *z == gid).unwrap()
         })
     }
 }
+
+/// One level of zones above a space: the zones of the level below as
+/// nodes, laid out into zones of their own.
+struct Level {
+    meta: SpaceMeta,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+/// Walks from `start` to `end` in a space whose levels of zones are
+/// `above`, lowest first, marking the solution on every level. Returns the
+/// edges of the path in order. The solution flags on every level must be
+/// clear.
+fn route(space: &impl Space, nodes: &[Node], edges: &mut [Edge], above: &mut [Level], start: usize, end: usize) -> Result<Vec<usize>> {
+    let (start_zone, end_zone) = (nodes[start].group, nodes[end].group);
+    let Some((level, rest)) = above.split_first_mut() else {
+        return Ok(space.solve(edges, start, end).collect())
+    };
+    if start_zone == end_zone {
+        return Ok(space.solve(edges, start, end).collect())
+    }
+    let Level{ meta, nodes: meta_nodes, edges: meta_edges } = level;
+    let zone = |group: Option<usize>| group.and_then(|group| meta.zone_index(group))
+        .ok_or(Error::MessageError("endpoint is not in a zone"));
+    let (meta_start, meta_end) = (zone(start_zone)?, zone(end_zone)?);
+    let borders = route(meta, meta_nodes, meta_edges, rest, meta_start, meta_end)?;
+
+    let mut path = Vec::new();
+    let mut gateways = Vec::with_capacity(borders.len());
+    let mut start = start;
+    for border in borders {
+        let gateway = meta.gateway(border)
+            .ok_or(Error::MessageError("no gateway through a border on the solution"))?;
+        let (near, far): (Vec<_>, Vec<_>) = space.edge_nodes(gateway)
+            .partition(|nid| nodes[*nid].group == nodes[start].group);
+        let (Some(near), Some(far)) = (near.first(), far.first()) else {
+            return Err(Error::MessageError("gateway does not lead out of the zone"))
+        };
+        path.extend(space.solve(edges, start, *near));
+        path.push(gateway);
+        gateways.push(gateway);
+        start = *far;
+    }
+    path.extend(space.solve(edges, start, end));
+    // Marked only once every zone is solved, so that walking the solution
+    // through one zone never wanders out through its gateway.
+    for gateway in gateways {
+        edges[gateway].solution = true;
+    }
+    Ok(path)
+}
+
+/// How long a solution should be, in steps within zones, and how many
+/// zones it should cross. `None` is no limit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Target {
+    pub min_solution_length: usize,
+    pub max_solution_length: Option<usize>,
+    pub min_solution_zones: usize,
+    pub max_solution_zones: Option<usize>,
+}
+
+impl Target {
+    pub fn accepts(&self, solution_length: usize, solution_zones: usize) -> bool {
+        solution_length >= self.min_solution_length
+            && self.max_solution_length.is_none_or(|max| solution_length <= max)
+            && solution_zones >= self.min_solution_zones
+            && self.max_solution_zones.is_none_or(|max| solution_zones <= max)
+    }
+}
+
+/// How many gateways to open between zones. One gateway through each
+/// border of a tree of zones is the default; more gateways, or extra
+/// borders, make loops between zones.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Gateways {
+    /// How many gateways to open through each open border, if it is long
+    /// enough.
+    pub per_border: usize,
+    /// The fraction of closed borders within each zone of the level above
+    /// to open as well.
+    pub extra_borders: f64,
+}
+
+impl Default for Gateways {
+    fn default() -> Self {
+        Self{
+            per_border: 1,
+            extra_borders: 0.0,
+        }
+    }
+}
+
+/// A stack of levels of zones over a laid-out space. The zones of the space
+/// are laid out into bigger zones, and those into bigger zones still, one
+/// level for each number of roots, with a gateway opened through each
+/// border that the level above crosses.
+pub struct Hierarchy {
+    levels: Vec<Level>,
+}
+
+impl Hierarchy {
+    /// Builds a level for each number of `roots`, lowest first, and opens
+    /// the gateways from the top down. The last level must have one root,
+    /// so that the whole maze is joined, and there must be a level at all
+    /// if the space has more than one zone.
+    pub fn new(space: &impl Space, nodes: &[Node], edges: &mut [Edge], roots: &[usize], rng: &mut impl rand::Rng) -> Result<Self> {
+        Self::with_gateways(space, nodes, edges, roots, Gateways::default(), rng)
+    }
+
+    /// Like [`Hierarchy::new`], but opens gateways as `gateways` says. With
+    /// loops between zones, [`Hierarchy::solve_shortest`] finds shorter
+    /// routes than [`Hierarchy::solve`].
+    pub fn with_gateways(space: &impl Space, nodes: &[Node], edges: &mut [Edge], roots: &[usize], gateways: Gateways, rng: &mut impl rand::Rng) -> Result<Self> {
+        if roots.last().is_some_and(|r| *r != 1) {
+            return Err(Error::MessageError("the top level must have exactly one root"))
+        }
+        if roots.is_empty() && nodes.iter().filter(|node| node.root).count() > 1 {
+            return Err(Error::MessageError("the zones need a level above to join them"))
+        }
+        let mut levels = Vec::<Level>::with_capacity(roots.len());
+        for level_roots in roots.iter() {
+            let meta = match levels.last() {
+                None => SpaceMeta::new(space, edges, nodes),
+                Some(below) => SpaceMeta::new(&below.meta, &below.edges, &below.nodes),
+            };
+            if *level_roots > meta.num_nodes() {
+                return Err(Error::MessageError("a level has more roots than there are zones below it"))
+            }
+            let (nodes, edges) = meta.layout(*level_roots, rng)?;
+            levels.push(Level{
+                meta,
+                nodes,
+                edges,
+            });
+        }
+        for lid in (1..levels.len()).rev() {
+            let (below, above) = levels.split_at_mut(lid);
+            let (Level{ meta, nodes, edges }, below) = (&mut above[0], &mut below[lid - 1]);
+            meta.open_gateways_with(nodes, edges, &mut below.edges, &gateways, rng);
+        }
+        if let Some(Level{ meta, nodes, edges: meta_edges }) = levels.first_mut() {
+            meta.open_gateways_with(nodes, meta_edges, edges, &gateways, rng);
+        }
+        Ok(Self{
+            levels,
+        })
+    }
+
+    /// How many levels there are above the space.
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The nodes of the space, grouped by the zones of the highest level
+    /// that has more than one, for [`Endpoints::DifferentZones`].
+    pub fn top_nodes(&self, nodes: &[Node]) -> Vec<Node> {
+        let top = self.levels.iter()
+            .rposition(|level| level.meta.num_nodes() > 1)
+            .map_or(0, |lid| lid + 1);
+        nodes.iter()
+            .map(|node| {
+                let group = node.group.and_then(|group| {
+                    self.levels[..top].iter().enumerate().try_fold(group, |group, (lid, level)| {
+                        let zone = level.meta.zone_index(group)?;
+                        if lid + 1 == top {
+                            Some(zone)
+                        } else {
+                            level.nodes[zone].group
+                        }
+                    })
+                });
+                Node{
+                    group,
+                    root: node.root,
+                }
+            })
+            .collect()
+    }
+
+    /// Picks up to `tries` random pairs of endpoints, and returns the first
+    /// whose solution `target` accepts. The solution is measured along the
+    /// same path [`Hierarchy::solve`] would take, but not marked.
+    pub fn find_endpoints(&mut self, space: &impl Space, nodes: &[Node], edges: &[Edge], target: &Target, tries: usize, rng: &mut impl rand::Rng) -> Option<(usize, usize)> {
+        // Solved on a copy, so the solution flags already marked are left
+        // alone.
+        let mut scratch = edges.iter()
+            .map(|e| Edge{ direction: e.direction, solution: false })
+            .collect::<Vec<_>>();
+        for _ in 0..tries {
+            let (start, end) = space.get_endpoints(rng)?;
+            self.clear_levels();
+            let path = route(space, nodes, &mut scratch, &mut self.levels, start, end);
+            let Ok(path) = path else { continue };
+            path.iter().for_each(|eid| scratch[*eid].solution = false);
+            let gateways = path.iter().filter(|eid| edges[**eid].direction == EdgeDirection::Border).count();
+            if target.accepts(path.len() - gateways, gateways + 1) {
+                return Some((start, end))
+            }
+        }
+        None
+    }
+
+    /// Solves the maze from `start` to `end` through every level, marking
+    /// the solution, and returns the edges of the path in order. Any
+    /// solution marked before is cleared first.
+    pub fn solve(&mut self, space: &impl Space, nodes: &[Node], edges: &mut [Edge], start: usize, end: usize) -> Result<Vec<usize>> {
+        edges.iter_mut().for_each(|e| e.solution = false);
+        self.clear_levels();
+        route(space, nodes, edges, &mut self.levels, start, end)
+    }
+
+    /// Clears the solution on every level above the space.
+    fn clear_levels(&mut self) {
+        self.levels.iter_mut()
+            .flat_map(|level| level.edges.iter_mut())
+            .for_each(|e| e.solution = false);
+    }
+
+    /// Solves the maze from `start` to `end` by the fewest steps, even
+    /// where the zones make loops. Routes over the zones of the lowest
+    /// level, from gateway to gateway, taking the shortest way across each
+    /// zone and any of the gateways through each open border. Any solution
+    /// marked before is cleared, then the new one is marked in the space.
+    /// Returns the edges of the path in order, or `None` if `end` cannot be
+    /// reached.
+    pub fn solve_shortest(&self, space: &impl Space, nodes: &[Node], edges: &mut [Edge], start: usize, end: usize) -> Option<Vec<usize>> {
+        use std::{cmp::Reverse, collections::BinaryHeap};
+        edges.iter_mut().for_each(|e| e.solution = false);
+        let within = |edges: &[Edge], from: usize, to: usize| {
+            let group = nodes[from].group;
+            search(space, edges, from, to, |eid| space.edge_nodes(eid).all(|nid| nodes[nid].group == group))
+        };
+        // Each way out of a zone, as the node inside, the gateway, and the
+        // node beyond it.
+        let exits = match self.levels.first() {
+            Some(Level{ meta, edges: meta_edges, .. }) => (0..meta.num_edges())
+                .filter(|bid| meta_edges[*bid].direction.is_open())
+                .flat_map(|bid| meta.gateways(bid).iter().copied())
+                .filter(|gateway| edges[*gateway].direction.is_open())
+                .flat_map(|gateway| {
+                    let mut ends = space.edge_nodes(gateway);
+                    let (a, b) = (ends.next().unwrap(), ends.next().unwrap());
+                    [(a, gateway, b), (b, gateway, a)]
+                })
+                .collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+
+        let mut best = vec![usize::MAX; space.num_nodes()];
+        let mut came_by = vec![None; space.num_nodes()];
+        let mut visiting = BinaryHeap::from([Reverse((0, start))]);
+        best[start] = 0;
+        while let Some(Reverse((steps, at))) = visiting.pop() {
+            if at == end {
+                break
+            }
+            if steps > best[at] {
+                continue
+            }
+            let ways = exits.iter()
+                .filter(|(near, _gateway, _far)| nodes[*near].group == nodes[at].group)
+                .filter_map(|(near, gateway, far)| {
+                    let mut walk = within(edges, at, *near)?;
+                    walk.push(*gateway);
+                    Some((*far, walk))
+                })
+                .chain(within(edges, at, end).map(|walk| (end, walk)));
+            for (to, walk) in ways {
+                if steps + walk.len() < best[to] {
+                    best[to] = steps + walk.len();
+                    visiting.push(Reverse((best[to], to)));
+                    came_by[to] = Some((at, walk));
+                }
+            }
+        }
+        if best[end] == usize::MAX {
+            return None
+        }
+        let mut walks = Vec::new();
+        let mut at = end;
+        while let Some((from, walk)) = came_by[at].take() {
+            walks.push(walk);
+            at = from;
+        }
+        let path = walks.into_iter().rev().flatten().collect::<Vec<_>>();
+        path.iter().for_each(|eid| edges[*eid].solution = true);
+        Some(path)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::space_square::SpaceSquare;
+    use super::*;
+    use rand::SeedableRng;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        init_tests();
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(30, 30);
+        let (nodes, mut edges) = space.layout(24, &mut rng).unwrap();
+        assert!(Hierarchy::new(&space, &nodes, &mut edges, &[6, 2], &mut rng).is_err());
+        assert!(Hierarchy::new(&space, &nodes, &mut edges, &[], &mut rng).is_err());
+        assert!(matches!(
+            Hierarchy::new(&space, &nodes, &mut edges, &[30, 1], &mut rng),
+            Err(Error::MessageError("a level has more roots than there are zones below it"))
+        ));
+        let mut hierarchy = Hierarchy::new(&space, &nodes, &mut edges, &[6, 2, 1], &mut rng).unwrap();
+        assert_eq!(hierarchy.depth(), 3);
+        assert_eq!(hierarchy.levels[0].meta.num_nodes(), 24);
+        assert_eq!(hierarchy.levels[1].meta.num_nodes(), 6);
+        assert_eq!(hierarchy.levels[2].meta.num_nodes(), 2);
+        // A tree over 24 zones needs 23 gateways.
+        let gateways = edges.iter().filter(|e| e.direction == EdgeDirection::Border).count();
+        assert_eq!(gateways, 23);
+        let top = hierarchy.top_nodes(&nodes);
+        assert!(top.iter().all(|n| n.group.is_some_and(|g| g < 2)));
+        let (start, end) = space.choose_endpoints(&top, &edges, Endpoints::DifferentZones, &mut rng).unwrap();
+        assert_ne!(top[start].group, top[end].group);
+
+        let (start, end) = (0, space.num_nodes() - 1);
+        let path = hierarchy.solve(&space, &nodes, &mut edges, start, end).unwrap();
+        let mut nid = start;
+        for eid in path.iter() {
+            assert!(edges[*eid].direction.is_open(), "edge {eid} is {:?}", edges[*eid].direction);
+            assert!(edges[*eid].solution, "edge {eid} is not marked");
+            nid = space.edge_nodes(*eid).find(|other| *other != nid).unwrap();
+        }
+        assert_eq!(nid, end);
+        assert_eq!(edges.iter().filter(|e| e.solution).count(), path.len());
+        assert_eq!(Some(path), space.solve_search(&mut edges, start, end));
+    }
+
+    #[test]
+    fn test_single_zone() {
+        init_tests();
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(4);
+        let space = SpaceSquare::new(10, 10);
+        let end = space.num_nodes() - 1;
+        for (roots, levels) in [(1, &[1][..]), (1, &[1, 1]), (6, &[1, 1]), (6, &[2, 1, 1])] {
+            let (nodes, mut edges) = space.layout(roots, &mut rng).unwrap();
+            let mut hierarchy = Hierarchy::new(&space, &nodes, &mut edges, levels, &mut rng).unwrap();
+            assert_eq!(hierarchy.depth(), levels.len());
+            let gateways = edges.iter().filter(|e| e.direction == EdgeDirection::Border).count();
+            assert_eq!(gateways, roots - 1, "{roots} roots under {levels:?}");
+            let path = hierarchy.solve(&space, &nodes, &mut edges, 0, end).unwrap();
+            assert_eq!(path.iter().fold(0, |nid, eid| follow_edge(&space, nid, *eid).unwrap()), end);
+        }
+    }
+
+    #[test]
+    fn test_solve_again() {
+        init_tests();
+        let space = SpaceSquare::new(20, 20);
+        for seed in 0..6 {
+            let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(seed);
+            let (nodes, mut edges) = space.layout(24, &mut rng).unwrap();
+            let mut hierarchy = Hierarchy::new(&space, &nodes, &mut edges, &[6, 2, 1], &mut rng).unwrap();
+            for _ in 0..3 {
+                let (start, end) = space.get_endpoints(&mut rng).unwrap();
+                let path = hierarchy.solve(&space, &nodes, &mut edges, start, end).unwrap();
+                let nid = path.iter().fold(start, |nid, eid| {
+                    assert!(edges[*eid].direction.is_open() && edges[*eid].solution, "seed {seed} edge {eid}");
+                    follow_edge(&space, nid, *eid).unwrap()
+                });
+                assert_eq!(nid, end, "seed {seed}");
+                assert_eq!(edges.iter().filter(|e| e.solution).count(), path.len(), "seed {seed}");
+                // The zones make a tree, so there is only one way.
+                assert_eq!(Some(path.len()), search(&space, &edges, start, end, |_eid| true).map(|p| p.len()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_endpoints() {
+        init_tests();
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(2);
+        let space = SpaceSquare::new(20, 20);
+        let (nodes, mut edges) = space.layout(6, &mut rng).unwrap();
+        // Loops between zones, so that the shortest way is not always the
+        // way the solution goes.
+        let gateways = Gateways{
+            per_border: 2,
+            extra_borders: 1.0,
+        };
+        let mut hierarchy = Hierarchy::with_gateways(&space, &nodes, &mut edges, &[1], gateways, &mut rng).unwrap();
+        let target = Target{
+            min_solution_length: 40,
+            min_solution_zones: 3,
+            ..Default::default()
+        };
+        assert!(!target.accepts(39, 3) && !target.accepts(40, 2) && target.accepts(40, 3));
+        let measure = |edges: &[Edge], path: &[usize]| {
+            let gateways = path.iter().filter(|eid| edges[**eid].direction == EdgeDirection::Border).count();
+            (path.len() - gateways, gateways + 1)
+        };
+        for _ in 0..20 {
+            // The pair that find_endpoints will try first.
+            let (start, end) = space.get_endpoints(&mut rng.clone()).unwrap();
+            let path = hierarchy.solve(&space, &nodes, &mut edges, start, end).unwrap();
+            let (length, zones) = measure(&edges, &path);
+            let exact = Target{
+                min_solution_length: length,
+                max_solution_length: Some(length),
+                min_solution_zones: zones,
+                max_solution_zones: Some(zones),
+            };
+            let longer = Target{
+                min_solution_length: length + 1,
+                ..exact
+            };
+            let marked = edges.iter().map(|e| e.solution).collect::<Vec<_>>();
+            assert_eq!(hierarchy.find_endpoints(&space, &nodes, &edges, &longer, 1, &mut rng.clone()), None);
+            assert_eq!(hierarchy.find_endpoints(&space, &nodes, &edges, &exact, 1, &mut rng), Some((start, end)));
+            assert_eq!(marked, edges.iter().map(|e| e.solution).collect::<Vec<_>>());
+        }
+        let (start, end) = hierarchy.find_endpoints(&space, &nodes, &edges, &target, 500, &mut rng).unwrap();
+        let path = hierarchy.solve(&space, &nodes, &mut edges, start, end).unwrap();
+        let (length, zones) = measure(&edges, &path);
+        assert!(target.accepts(length, zones));
+
+        let impossible = Target{
+            min_solution_length: space.num_nodes(),
+            ..Default::default()
+        };
+        assert_eq!(hierarchy.find_endpoints(&space, &nodes, &edges, &impossible, 20, &mut rng), None);
+    }
+
+    #[test]
+    fn test_gateways() {
+        init_tests();
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(3);
+        let space = SpaceSquare::new(20, 20);
+        let (nodes, mut edges) = space.layout(12, &mut rng).unwrap();
+        let gateways = Gateways{
+            per_border: 2,
+            extra_borders: 1.0,
+        };
+        let mut hierarchy = Hierarchy::with_gateways(&space, &nodes, &mut edges, &[3, 1], gateways, &mut rng).unwrap();
+        let level = &hierarchy.levels[0];
+        assert!(level.edges.iter().any(|e| e.direction == EdgeDirection::Open));
+        let open_borders = level.edges.iter().filter(|e| e.direction.is_open()).count();
+        assert!(open_borders > 11);
+        for bid in 0..level.meta.num_edges() {
+            let count = level.meta.gateways(bid).len();
+            assert!(count <= 2);
+            assert_eq!(count > 0, level.edges[bid].direction.is_open(), "border {bid}");
+        }
+        let opened = edges.iter().filter(|e| e.direction == EdgeDirection::Border).count();
+        assert!(opened > open_borders);
+
+        let walk = |edges: &[Edge], path: &[usize]| path.iter().fold(0, |nid, eid| {
+            assert!(edges[*eid].direction.is_open() && edges[*eid].solution, "edge {eid}");
+            space.edge_nodes(*eid).find(|other| *other != nid).unwrap()
+        });
+        let end = space.num_nodes() - 1;
+        let shortest = hierarchy.solve_shortest(&space, &nodes, &mut edges, 0, end).unwrap();
+        assert_eq!(walk(&edges, &shortest), end);
+        assert_eq!(edges.iter().filter(|e| e.solution).count(), shortest.len());
+        let tree = hierarchy.solve(&space, &nodes, &mut edges, 0, end).unwrap();
+        assert_eq!(walk(&edges, &tree), end);
+        assert!(shortest.len() <= tree.len());
+
+        // Never longer than the way through the tree of zones, and with
+        // loops, sometimes shorter.
+        let mut shorter = 0;
+        for _ in 0..20 {
+            let (start, end) = space.get_endpoints(&mut rng).unwrap();
+            let tree = hierarchy.solve(&space, &nodes, &mut edges, start, end).unwrap();
+            let shortest = hierarchy.solve_shortest(&space, &nodes, &mut edges, start, end).unwrap();
+            assert_eq!(shortest.iter().fold(start, |nid, eid| follow_edge(&space, nid, *eid).unwrap()), end);
+            assert_eq!(Some(shortest.len()), search(&space, &edges, start, end, |_eid| true).map(|p| p.len()));
+            assert!(shortest.len() <= tree.len());
+            if shortest.len() < tree.len() {
+                shorter += 1;
+            }
+        }
+        assert!(shorter > 0);
+
+        // A node walled in on every side cannot be reached.
+        for eid in space.node_edges(end).collect::<Vec<_>>() {
+            edges[eid].direction = EdgeDirection::Closed;
+        }
+        assert_eq!(hierarchy.solve_shortest(&space, &nodes, &mut edges, 0, end), None);
+    }
+}
@@ -0,0 +1,370 @@
+use std::iter::Iterator;
+use std::f32::consts::TAU;
+use super::space::Space;
+use super::render::{SpaceRenderer, render_svg_2d};
+use super::edge::Edge;
+use super::node::Node;
+
+/// How many cells the first ring around the centre is split into.
+const FIRST_RING: usize = 6;
+
+/// How many straight segments approximate the arc along one side of a cell
+/// when it is drawn as a polygon.
+const ARC_STEPS: usize = 4;
+
+/// A circular grid of concentric rings around a single centre cell.
+///
+/// Ring `i` runs from radius `i - 0.5` to `i + 0.5`. Each ring has as many
+/// cells as the ring inside it, or a whole multiple of that, chosen so the
+/// cells stay roughly square. Every cell outside the centre therefore has
+/// exactly one inward neighbour, its "parent".
+#[derive(Debug)]
+pub struct SpacePolar {
+    ring_sizes: Vec<usize>,
+    node_offsets: Vec<usize>,
+    edge_offsets: Vec<usize>,
+}
+
+impl SpacePolar {
+    pub fn new(rings: usize) -> Self {
+        let mut ring_sizes = Vec::<usize>::with_capacity(rings);
+        for ring in 0..rings {
+            let size = match ring {
+                0 => 1,
+                1 => FIRST_RING,
+                _ => {
+                    let prev = ring_sizes[ring - 1];
+                    let cell_width = TAU * ring as f32 / prev as f32;
+                    prev * (cell_width.round() as usize).max(1)
+                }
+            };
+            ring_sizes.push(size);
+        }
+        let mut node_offsets = Vec::<usize>::with_capacity(rings + 1);
+        let mut edge_offsets = Vec::<usize>::with_capacity(rings + 1);
+        let (mut nodes, mut edges) = (0, 0);
+        for (ring, size) in ring_sizes.iter().enumerate() {
+            node_offsets.push(nodes);
+            edge_offsets.push(edges);
+            nodes += size;
+            // Every ring but the centre has an inward edge and a sideways
+            // edge for each cell.
+            if ring > 0 {
+                edges += size * 2;
+            }
+        }
+        node_offsets.push(nodes);
+        edge_offsets.push(edges);
+        Self{
+            ring_sizes,
+            node_offsets,
+            edge_offsets,
+        }
+    }
+
+    pub fn rings(&self) -> usize {
+        self.ring_sizes.len()
+    }
+
+    pub fn ring_size(&self, ring: usize) -> usize {
+        self.ring_sizes[ring]
+    }
+
+    /// The width and height of the disc in cell units, for sizing a document.
+    pub fn extent(&self) -> (usize, usize) {
+        let size = (self.rings() * 2).saturating_sub(1);
+        (size, size)
+    }
+
+    /// The radius of the outer wall.
+    pub fn radius(&self) -> f32 {
+        self.rings() as f32 - 0.5
+    }
+
+    #[inline]
+    fn node_ring(&self, node_id: usize) -> (usize, usize) {
+        let ring = self.node_offsets.partition_point(|o| *o <= node_id) - 1;
+        (ring, node_id - self.node_offsets[ring])
+    }
+
+    #[inline]
+    fn edge_ring(&self, edge_id: usize) -> (usize, usize) {
+        let ring = self.edge_offsets.partition_point(|o| *o <= edge_id) - 1;
+        (ring, edge_id - self.edge_offsets[ring])
+    }
+
+    #[inline]
+    fn ring_node(&self, ring: usize, idx: usize) -> usize {
+        self.node_offsets[ring] + idx
+    }
+
+    /// How many cells of `ring` share a single parent in the ring inside it.
+    #[inline]
+    fn ratio(&self, ring: usize) -> usize {
+        self.ring_sizes[ring] / self.ring_sizes[ring - 1]
+    }
+
+    #[inline]
+    fn angle(&self, ring: usize, idx: f32) -> f32 {
+        TAU * idx / self.ring_sizes[ring] as f32
+    }
+
+    #[inline]
+    fn centre(&self) -> f32 {
+        self.rings() as f32 - 1.0
+    }
+
+    #[inline]
+    fn polar(&self, radius: f32, angle: f32) -> [f32; 2] {
+        let (sin, cos) = angle.sin_cos();
+        let centre = self.centre();
+        [centre + radius * sin, centre + radius * cos]
+    }
+
+    fn arc(&self, radius: f32, from: f32, to: f32, steps: usize) -> impl Iterator<Item=[f32; 2]> + '_ {
+        (0..=steps).map(move |step| {
+            self.polar(radius, from + (to - from) * step as f32 / steps as f32)
+        })
+    }
+}
+
+impl Space for SpacePolar {
+    fn num_nodes(&self) -> usize {
+        self.node_offsets[self.rings()]
+    }
+    fn num_edges(&self) -> usize {
+        self.edge_offsets[self.rings()]
+    }
+    fn node_edges(&self, node_id: usize) -> impl Iterator<Item=usize> {
+        let (ring, idx) = self.node_ring(node_id);
+        let size = self.ring_sizes[ring];
+        let (inward, sideways) = if ring > 0 {
+            let base = self.edge_offsets[ring];
+            (
+                Some(base + idx),
+                Some([base + size + idx, base + size + (idx + size - 1) % size]),
+            )
+        } else {
+            (None, None)
+        };
+        let outward = if ring + 1 < self.rings() {
+            let ratio = self.ratio(ring + 1);
+            let base = self.edge_offsets[ring + 1];
+            (base + idx * ratio)..(base + (idx + 1) * ratio)
+        } else {
+            0..0
+        };
+        inward.into_iter()
+            .chain(sideways.into_iter().flatten())
+            .chain(outward)
+    }
+    fn edge_nodes(&self, edge_id: usize) -> impl Iterator<Item=usize> {
+        let (ring, k) = self.edge_ring(edge_id);
+        let size = self.ring_sizes[ring];
+        let (a, b) = if k < size {
+            ((ring, k), (ring - 1, k / self.ratio(ring)))
+        } else {
+            let idx = k - size;
+            ((ring, idx), (ring, (idx + 1) % size))
+        };
+        [a, b].into_iter().map(|(ring, idx)| self.ring_node(ring, idx))
+    }
+}
+
+impl SpaceRenderer<2> for SpacePolar {
+    fn edge_position(&self, edge_id: usize) -> ([f32; 2], [f32; 2]) {
+        let mut end_nodes = self.edge_nodes(edge_id);
+        let start = end_nodes.next().unwrap();
+        let end = end_nodes.next().unwrap();
+        (self.node_position(start), self.node_position(end))
+    }
+    fn node_position(&self, node_id: usize) -> [f32; 2] {
+        let (ring, idx) = self.node_ring(node_id);
+        self.polar(ring as f32, self.angle(ring, idx as f32 + 0.5))
+    }
+    fn wall_position(&self, edge_id: usize) -> Option<([f32; 2], [f32; 2])> {
+        let (ring, k) = self.edge_ring(edge_id);
+        let size = self.ring_sizes[ring];
+        let inner = ring as f32 - 0.5;
+        if k < size {
+            Some((
+                self.polar(inner, self.angle(ring, k as f32)),
+                self.polar(inner, self.angle(ring, k as f32 + 1.0)),
+            ))
+        } else {
+            let angle = self.angle(ring, (k - size) as f32 + 1.0);
+            Some((
+                self.polar(inner, angle),
+                self.polar(inner + 1.0, angle),
+            ))
+        }
+    }
+    /// Walls between rings are arcs; walls between cells of a ring are
+    /// straight lines from the centre outward.
+    fn wall_radius(&self, edge_id: usize) -> Option<f32> {
+        let (ring, k) = self.edge_ring(edge_id);
+        if k < self.ring_sizes[ring] {
+            Some(ring as f32 - 0.5)
+        } else {
+            None
+        }
+    }
+    fn node_outline(&self, node_id: usize) -> Option<Vec<[f32; 2]>> {
+        let (ring, idx) = self.node_ring(node_id);
+        if ring == 0 {
+            return Some(self.arc(0.5, 0.0, TAU, ARC_STEPS * FIRST_RING).collect())
+        }
+        let (from, to) = (self.angle(ring, idx as f32), self.angle(ring, idx as f32 + 1.0));
+        let (inner, outer) = (ring as f32 - 0.5, ring as f32 + 0.5);
+        Some(
+            self.arc(inner, from, to, ARC_STEPS)
+                .chain(self.arc(outer, to, from, ARC_STEPS))
+                .collect()
+        )
+    }
+    fn border_walls(&self) -> Option<Vec<([f32; 2], [f32; 2])>> {
+        // An empty disc has no rim.
+        let ring = self.rings().checked_sub(1)?;
+        let points = self.arc(self.radius(), 0.0, TAU, self.ring_sizes[ring] * ARC_STEPS).collect::<Vec<_>>();
+        Some(
+            points.windows(2).map(|w| (w[0], w[1])).collect()
+        )
+    }
+}
+
+
+/// Renders a polar maze, sized to fit the disc.
+pub fn render_svg_polar(
+    space: &SpacePolar,
+    edges: &[Edge],
+    nodes: &[Node],
+    scale: f32,
+    start: usize,
+    end: usize,
+) -> svg::Document {
+    let (width, height) = space.extent();
+    render_svg_2d(space, edges, nodes, width, height, scale, start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::space::Space;
+    use crate::edge::EdgeDirection;
+    use super::*;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_ring_sizes() {
+        init_tests();
+        let space = SpacePolar::new(7);
+        let sizes = (0..space.rings()).map(|r| space.ring_size(r)).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![1, 6, 12, 24, 24, 24, 48]);
+        assert_eq!(space.num_nodes(), sizes.iter().sum::<usize>());
+
+        let empty = SpacePolar::new(0);
+        assert_eq!(empty.num_nodes(), 0);
+        assert!(empty.border_walls().is_none());
+    }
+
+    #[test]
+    fn test_num_edges() {
+        init_tests();
+        let space = SpacePolar::new(5);
+        let count = (0..space.num_nodes())
+            .map(|nid| space.node_edges(nid).count())
+            .sum::<usize>();
+        assert_eq!(space.num_edges() * 2, count);
+    }
+
+    #[test]
+    fn test_node_edges() {
+        init_tests();
+        let space = SpacePolar::new(3);
+        macro_rules! test {
+            ($idx:literal => $edges:expr) => {
+                {
+                    let mut edges = space.node_edges($idx).collect::<Vec<_>>();
+                    edges.sort();
+                    assert_eq!(edges, $edges);
+                }
+            }
+        }
+        test!(0 => vec![0, 1, 2, 3, 4, 5]);
+        test!(1 => vec![0, 6, 11, 12, 13]);
+        test!(6 => vec![5, 10, 11, 22, 23]);
+        test!(7 => vec![12, 24, 35]);
+    }
+
+    #[test]
+    fn test_edge_nodes() {
+        init_tests();
+        let space = SpacePolar::new(6);
+        for nid in 0..space.num_nodes() {
+            for eid in space.node_edges(nid) {
+                let nodes = space.edge_nodes(eid).collect::<Vec<_>>();
+                assert_eq!(nodes.len(), 2);
+                assert!(nodes.contains(&nid), "edge {eid} does not touch node {nid}: {nodes:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_neighbours() {
+        init_tests();
+        let space = SpacePolar::new(7);
+        for nid in 0..space.num_nodes() {
+            let (ring, idx) = space.node_ring(nid);
+            let size = space.ring_size(ring);
+            let rings = space.node_edges(nid)
+                .map(|eid| space.node_ring(space.edge_nodes(eid).find(|other| *other != nid).unwrap()))
+                .collect::<Vec<_>>();
+            let count = |r: usize| rings.iter().filter(|(other, _)| *other == r).count();
+            if ring == 0 {
+                assert_eq!(count(1), space.ring_size(1));
+                continue
+            }
+            // One parent, and the cells either side, wrapping round.
+            assert_eq!(count(ring - 1), 1, "node {nid}");
+            assert!(rings.contains(&(ring, (idx + 1) % size)), "node {nid}");
+            assert!(rings.contains(&(ring, (idx + size - 1) % size)), "node {nid}");
+            let outward = if ring + 1 < space.rings() { space.ratio(ring + 1) } else { 0 };
+            assert_eq!(count(ring + 1), outward, "node {nid}");
+            assert_eq!(rings.len(), 3 + outward, "node {nid}");
+        }
+        // No two edges join the same cells.
+        let mut pairs = (0..space.num_edges())
+            .map(|eid| {
+                let mut pair = space.edge_nodes(eid).collect::<Vec<_>>();
+                pair.sort();
+                pair
+            })
+            .collect::<Vec<_>>();
+        pairs.sort();
+        pairs.dedup();
+        assert_eq!(pairs.len(), space.num_edges());
+    }
+
+    #[test]
+    fn test_render() {
+        init_tests();
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(2);
+        let space = SpacePolar::new(5);
+        let (nodes, edges) = space.layout(1, &mut rng).unwrap();
+        let svg = render_svg_polar(&space, &edges, &nodes, 10.0, 0, space.num_nodes() - 1).to_string();
+        // Walls between rings are arcs, walls within a ring are lines.
+        let closed = |arc: bool| (0..space.num_edges())
+            .filter(|eid| edges[*eid].direction == EdgeDirection::Closed && space.wall_radius(*eid).is_some() == arc)
+            .count();
+        assert_eq!(svg.matches(",0,0,1,").count(), closed(true));
+        assert!(closed(false) > 0);
+    }
+}
@@ -0,0 +1,373 @@
+use std::iter::Iterator;
+use std::collections::HashSet;
+use super::space::Space;
+use super::space_graph::SpaceGraph;
+use super::render::SpaceRenderer;
+
+macro_rules! rq {
+    ( ( $lhs:expr ) / ( $rhs:expr )) => {
+        (
+            ($lhs as usize / $rhs as usize),
+            ($lhs as usize % $rhs as usize)
+        )
+    }
+}
+
+/// Steps within a face, by row and column.
+const DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+/// The faces of the cube in the order their nodes are numbered, with where
+/// each sits in the unfolded net, by block row and block column:
+///
+/// ```text
+///       top
+/// left front right back
+///       bottom
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Face {
+    Front,
+    Right,
+    Back,
+    Left,
+    Top,
+    Bottom,
+}
+
+const FACES: [Face; 6] = [Face::Front, Face::Right, Face::Back, Face::Left, Face::Top, Face::Bottom];
+
+impl Face {
+    fn net_block(&self) -> (usize, usize) {
+        match self {
+            Face::Top => (0, 1),
+            Face::Left => (1, 0),
+            Face::Front => (1, 1),
+            Face::Right => (1, 2),
+            Face::Back => (1, 3),
+            Face::Bottom => (2, 1),
+        }
+    }
+}
+
+/// A maze on the surface of a cube, each face a square grid, with every
+/// cell connected to its neighbours across the seams between faces. There
+/// is no border at all.
+///
+/// Cells are found in the cube by twice the position of their centre, so
+/// every coordinate is a whole number: `0` or `2 * size` across the face
+/// and odd along it.
+#[derive(Debug)]
+pub struct SpaceSphere {
+    size: usize,
+    neighbours: Vec<[usize; 4]>,
+    graph: SpaceGraph,
+}
+
+impl SpaceSphere {
+    pub fn new(size: usize) -> Self {
+        let nodes = 6 * size * size;
+        let mut this = Self{
+            size,
+            neighbours: Vec::with_capacity(nodes),
+            graph: SpaceGraph::new(0, Vec::new()).unwrap(),
+        };
+        let mut pairs = Vec::<(usize, usize)>::with_capacity(nodes * 2);
+        let mut seen = HashSet::<(usize, usize)>::new();
+        for nid in 0..nodes {
+            let neighbours = DIRECTIONS.map(|step| this.step(nid, step));
+            for other in neighbours {
+                let key = (nid.min(other), nid.max(other));
+                if seen.insert(key) {
+                    pairs.push(key);
+                }
+            }
+            this.neighbours.push(neighbours);
+        }
+        this.graph = SpaceGraph::new(nodes, pairs).unwrap();
+        this
+    }
+
+    /// The unfolded net of the cube, for rendering in 2D.
+    pub fn net(&self) -> SphereNet<'_> {
+        SphereNet{
+            space: self,
+        }
+    }
+
+    /// The width and height of the unfolded net in cell units, for sizing a
+    /// document.
+    pub fn extent(&self) -> (usize, usize) {
+        (self.size * 4, self.size * 3)
+    }
+
+    #[inline]
+    fn face_cell(&self, node_id: usize) -> (Face, usize, usize) {
+        let (face, cell) = rq!((node_id) / (self.size * self.size));
+        let (row, col) = rq!((cell) / (self.size));
+        (FACES[face], row, col)
+    }
+
+    /// Twice the centre of a cell.
+    fn point(&self, face: Face, row: usize, col: usize) -> [isize; 3] {
+        let n = 2 * self.size as isize;
+        let (r, c) = (2 * row as isize + 1, 2 * col as isize + 1);
+        match face {
+            Face::Front => [c, r, 0],
+            Face::Right => [n, r, c],
+            Face::Back => [n - c, r, n],
+            Face::Left => [0, r, n - c],
+            Face::Top => [c, 0, n - r],
+            Face::Bottom => [c, n, r],
+        }
+    }
+
+    /// The cell whose centre is at twice the given point.
+    fn point_node(&self, p: [isize; 3]) -> usize {
+        let n = 2 * self.size as isize;
+        let [x, y, z] = p;
+        let (face, r, c) = if z == 0 {
+            (Face::Front, y, x)
+        } else if x == n {
+            (Face::Right, y, z)
+        } else if z == n {
+            (Face::Back, y, n - x)
+        } else if x == 0 {
+            (Face::Left, y, n - z)
+        } else if y == 0 {
+            (Face::Top, n - z, x)
+        } else {
+            (Face::Bottom, z, x)
+        };
+        let face = FACES.iter().position(|f| *f == face).unwrap();
+        let (row, col) = (((r - 1) / 2) as usize, ((c - 1) / 2) as usize);
+        (face * self.size + row) * self.size + col
+    }
+
+    /// The neighbour one step away within a face, or over the seam onto
+    /// the next face.
+    fn step(&self, node_id: usize, step: (isize, isize)) -> usize {
+        let n = 2 * self.size as isize;
+        let (face, row, col) = self.face_cell(node_id);
+        let p = self.point(face, row, col);
+        let next = |row: usize, col: usize| self.point(face, row, col);
+        // Moving by a whole cell is moving by two in doubled coordinates.
+        let along = {
+            let (dr, dc) = step;
+            let base = next(row, col);
+            let r = if dr != 0 { next(row + 1, col) } else { next(row, col + 1) };
+            let d = [r[0] - base[0], r[1] - base[1], r[2] - base[2]];
+            let sign = dr + dc;
+            [d[0] * sign, d[1] * sign, d[2] * sign]
+        };
+        let q = [p[0] + along[0], p[1] + along[1], p[2] + along[2]];
+        if q.iter().all(|v| (0..=n).contains(v)) {
+            return self.point_node(q)
+        }
+        // Over the seam, the neighbour is half a step along and half a step
+        // into the cube.
+        let inward = match face {
+            Face::Front => [0, 0, 1],
+            Face::Right => [-1, 0, 0],
+            Face::Back => [0, 0, -1],
+            Face::Left => [1, 0, 0],
+            Face::Top => [0, 1, 0],
+            Face::Bottom => [0, -1, 0],
+        };
+        self.point_node([
+            p[0] + along[0] / 2 + inward[0],
+            p[1] + along[1] / 2 + inward[1],
+            p[2] + along[2] / 2 + inward[2],
+        ])
+    }
+}
+
+impl Space for SpaceSphere {
+    fn num_nodes(&self) -> usize {
+        self.graph.num_nodes()
+    }
+    fn num_edges(&self) -> usize {
+        self.graph.num_edges()
+    }
+    fn node_edges(&self, node_id: usize) -> impl Iterator<Item=usize> {
+        self.graph.node_edges(node_id)
+    }
+    fn edge_nodes(&self, edge_id: usize) -> impl Iterator<Item=usize> {
+        self.graph.edge_nodes(edge_id)
+    }
+}
+
+impl SpaceRenderer<3> for SpaceSphere {
+    fn edge_position(&self, edge_id: usize) -> ([f32; 3], [f32; 3]) {
+        let mut end_nodes = self.edge_nodes(edge_id);
+        let start = end_nodes.next().unwrap();
+        let end = end_nodes.next().unwrap();
+        (self.node_position(start), self.node_position(end))
+    }
+    /// Cells are pushed out from the cube onto a sphere of the same width
+    /// around the origin.
+    fn node_position(&self, node_id: usize) -> [f32; 3] {
+        let (face, row, col) = self.face_cell(node_id);
+        let p = self.point(face, row, col);
+        let half = self.size as f32;
+        let v = p.map(|v| v as f32 - half);
+        let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        v.map(|v| v / length * half / 2.0)
+    }
+}
+
+/// The faces of a [`SpaceSphere`] unfolded into a cross. Walls across the
+/// seams that the unfolding cuts are drawn on both cells, and the seams
+/// are left open where a passage crosses them.
+pub struct SphereNet<'a> {
+    space: &'a SpaceSphere,
+}
+
+impl SphereNet<'_> {
+    #[inline]
+    fn net_position(&self, node_id: usize) -> [f32; 2] {
+        let (face, row, col) = self.space.face_cell(node_id);
+        let (block_row, block_col) = face.net_block();
+        let n = self.space.size;
+        [ (block_row * n + row) as f32, (block_col * n + col) as f32 ]
+    }
+
+    /// The step from `from` to `to`, if they are neighbours.
+    #[inline]
+    fn direction(&self, from: usize, to: usize) -> (f32, f32) {
+        let k = self.space.neighbours[from].iter().position(|n| *n == to).unwrap();
+        let (dr, dc) = DIRECTIONS[k];
+        (dr as f32, dc as f32)
+    }
+
+    #[inline]
+    fn is_cut(&self, a: usize, b: usize) -> bool {
+        let (pa, pb) = (self.net_position(a), self.net_position(b));
+        (pa[0] - pb[0]).abs() + (pa[1] - pb[1]).abs() > 1.5
+    }
+
+    fn ends(&self, edge_id: usize) -> (usize, usize) {
+        let mut end_nodes = self.space.edge_nodes(edge_id);
+        (end_nodes.next().unwrap(), end_nodes.next().unwrap())
+    }
+}
+
+impl SpaceRenderer<2> for SphereNet<'_> {
+    /// Across a cut seam, an edge ends just past the side of its first
+    /// cell, so its wall is drawn there.
+    fn edge_position(&self, edge_id: usize) -> ([f32; 2], [f32; 2]) {
+        let (a, b) = self.ends(edge_id);
+        let start = self.net_position(a);
+        if !self.is_cut(a, b) {
+            return (start, self.net_position(b))
+        }
+        let (dr, dc) = self.direction(a, b);
+        (start, [start[0] + dr, start[1] + dc])
+    }
+    fn node_position(&self, node_id: usize) -> [f32; 2] {
+        self.net_position(node_id)
+    }
+    fn wrap_position(&self, edge_id: usize) -> Option<([f32; 2], [f32; 2])> {
+        let (a, b) = self.ends(edge_id);
+        if !self.is_cut(a, b) {
+            return None
+        }
+        let (dr, dc) = self.direction(b, a);
+        let [y, x] = self.net_position(b);
+        let (y, x) = (y + dr * 0.5, x + dc * 0.5);
+        Some(if dr != 0.0 {
+            ([y, x - 0.5], [y, x + 0.5])
+        } else {
+            ([y - 0.5, x], [y + 0.5, x])
+        })
+    }
+    fn border_walls(&self) -> Option<Vec<([f32; 2], [f32; 2])>> {
+        Some(Vec::new())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::space::Space;
+    use super::*;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_neighbours() {
+        init_tests();
+        for size in 1..5 {
+            let space = SpaceSphere::new(size);
+            assert_eq!(space.num_nodes(), 6 * size * size);
+            assert_eq!(space.num_edges(), 12 * size * size);
+            for nid in 0..space.num_nodes() {
+                assert_eq!(space.node_edges(nid).count(), 4, "size {size} node {nid}");
+                for other in space.neighbours[nid] {
+                    assert_ne!(other, nid);
+                    assert!(space.neighbours[other].contains(&nid), "size {size}: {nid} -> {other} is one way");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_node_position() {
+        init_tests();
+        let space = SpaceSphere::new(3);
+        for eid in 0..space.num_edges() {
+            let (start, end) = SpaceRenderer::<3>::edge_position(&space, eid);
+            let length = start.iter().zip(end.iter()).map(|(a, b)| (a - b).powi(2)).sum::<f32>().sqrt();
+            assert!(length < 1.0, "edge {eid} spans {length}");
+        }
+    }
+
+    #[test]
+    fn test_net() {
+        init_tests();
+        let space = SpaceSphere::new(3);
+        let net = space.net();
+        for eid in 0..space.num_edges() {
+            let (start, end) = net.edge_position(eid);
+            let moved = (start[0] - end[0]).abs() + (start[1] - end[1]).abs();
+            assert_eq!(moved, 1.0, "edge {eid}: {start:?} {end:?}");
+        }
+    }
+
+    #[test]
+    fn test_seams() {
+        init_tests();
+        let size = 4;
+        let space = SpaceSphere::new(size);
+        for nid in 0..space.num_nodes() {
+            assert!(!space.node_on_border(nid));
+            // Cells along the edge of a face cross a seam for each side of
+            // the face they touch.
+            let (face, row, col) = space.face_cell(nid);
+            let seams = [row == 0, row == size - 1, col == 0, col == size - 1].iter()
+                .filter(|side| **side)
+                .count();
+            let crossing = space.neighbours[nid].iter()
+                .filter(|other| space.face_cell(**other).0 != face)
+                .count();
+            assert_eq!(crossing, seams, "node {nid}");
+
+            // Going straight on in any direction comes back round the cube
+            // after four faces.
+            for first in space.neighbours[nid] {
+                let (mut from, mut at) = (nid, first);
+                for _ in 1..4 * size {
+                    let back = space.neighbours[at].iter().position(|other| *other == from).unwrap();
+                    (from, at) = (at, space.neighbours[at][3 - back]);
+                }
+                assert_eq!(at, nid, "node {nid} towards {first}");
+            }
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use std::iter::Iterator;
-use super::space::Space;
+use super::space::{Space, search, follow_edge, open_edge};
 use super::render::SpaceRenderer;
+use super::mask::Mask;
+use super::edge::{Edge, EdgeDirection, EdgeKind};
+use super::node::Node;
 
 macro_rules! rq {
     ( ( $lhs:expr ) / ( $rhs:expr )) => {
@@ -11,10 +14,41 @@ macro_rules! rq {
     }
 }
 
+/// Which sides of a square grid connect back round to the opposite side.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    #[default]
+    None,
+    /// The east edge of the last column connects to the first column.
+    Cylinder,
+    /// As a cylinder, and the south edge of the last row also connects to
+    /// the first row.
+    Torus,
+}
+
+/// Which way a passage tunnels under a weave crossing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tunnel {
+    /// The tunnel runs east to west, under a cell open to the north and
+    /// south.
+    EastWest,
+    /// The tunnel runs north to south, under a cell open to the east and
+    /// west.
+    NorthSouth,
+}
+
 #[derive(Debug)]
 pub struct SpaceSquare {
     rows: usize,
     cols: usize,
+    wrap: Wrap,
+    mask: Option<Mask>,
+    /// The cells passages can tunnel under, in the order of their crossing
+    /// edges, which come after all the other edges.
+    crossings: Vec<usize>,
+    /// The tunnel under each cell and the index of its crossing, if any,
+    /// or empty if nothing is woven.
+    tunnels: Vec<Option<(Tunnel, usize)>>,
 }
 
 #[derive(Debug)]
@@ -38,7 +72,7 @@ impl<'a> SpaceSquareEdgeIterator<'a> {
         (self.row * self.space.edge_row_len()) + col
     }
     fn south(&self, row: usize) -> usize {
-        (row * self.space.edge_row_len()) + self.space.cols - 1 + self.col
+        (row * self.space.edge_row_len()) + self.space.east_len() + self.col
     }
 }
 
@@ -50,8 +84,12 @@ impl<'a> Iterator for SpaceSquareEdgeIterator<'a> {
                 0 => {
                     // North
                     self.step = 1;
-                    // If this is the first row, there are no North edges
+                    // If this is the first row, there are no North edges,
+                    // unless the grid wraps round to the last row.
                     if self.row == 0 {
+                        if self.space.wraps_rows() {
+                            return Some(self.south(self.space.rows-1))
+                        }
                         continue
                     }
 
@@ -62,8 +100,12 @@ impl<'a> Iterator for SpaceSquareEdgeIterator<'a> {
                 1 => {
                     // West
                     self.step = 2;
-                    // If this is the western-most node, there is no western edge.
+                    // If this is the western-most node, there is no western
+                    // edge, unless the grid wraps round to the last column.
                     if self.col == 0 {
+                        if self.space.wraps_cols() {
+                            return Some(self.east(self.space.cols-1))
+                        }
                         continue
                     }
                     // The west edge is the east edge of the previous node
@@ -75,7 +117,7 @@ impl<'a> Iterator for SpaceSquareEdgeIterator<'a> {
                 2 => {
                     self.step = 3;
                     // East
-                    if self.col >= self.space.cols - 1 {
+                    if self.col >= self.space.east_len() {
                         continue
                     }
                     return Some(
@@ -85,7 +127,7 @@ impl<'a> Iterator for SpaceSquareEdgeIterator<'a> {
                 3 => {
                     self.step = 4;
                     // South
-                    if self.row >= self.space.rows - 1 {
+                    if self.row >= self.space.south_len() {
                         continue
                     }
                     return Some(
@@ -124,7 +166,7 @@ impl<'a> SpaceSquareNodeIterator<'a> {
     }
 
     fn north(&self, row: usize) -> usize {
-        row * self.space.cols + self.col - (self.space.cols - 1)
+        row * self.space.cols + self.col - self.space.east_len()
     }
 }
 impl<'a> Iterator for SpaceSquareNodeIterator<'a> {
@@ -146,11 +188,9 @@ impl<'a> Iterator for SpaceSquareNodeIterator<'a> {
                     if self.is_horizontal() {
                         continue
                     }
-                    if self.col >= self.space.cols - 1 {
-                        continue
-                    }
+                    // The last column's east edge wraps round to the first.
                     return Some(
-                        self.west(self.col + 1)
+                        self.west((self.col + 1) % self.space.cols)
                     )
 
                 },
@@ -168,11 +208,9 @@ impl<'a> Iterator for SpaceSquareNodeIterator<'a> {
                     if !self.is_horizontal() {
                         continue
                     }
-                    if self.row >= self.space.rows {
-                        continue
-                    }
+                    // The last row's south edge wraps round to the first.
                     return Some(
-                        self.north(self.row + 1)
+                        self.north((self.row + 1) % self.space.rows)
                     )
                 },
                 _ => return None,
@@ -183,19 +221,304 @@ impl<'a> Iterator for SpaceSquareNodeIterator<'a> {
 
 impl SpaceSquare {
     pub fn new(rows: usize, cols: usize) -> Self {
+        Self::with_wrap(rows, cols, Wrap::None)
+    }
+
+    /// A grid whose sides connect back round to each other. A side only
+    /// wraps if it is at least three cells long.
+    pub fn with_wrap(rows: usize, cols: usize, wrap: Wrap) -> Self {
         Self{
             rows,
             cols,
+            wrap,
+            mask: None,
+            crossings: Vec::new(),
+            tunnels: Vec::new(),
         }
     }
+
+    /// A grid the size of a mask, where only the mask's enabled cells are
+    /// part of the maze. The enabled cells should all be connected.
+    pub fn with_mask(mask: Mask, wrap: Wrap) -> Self {
+        Self{
+            rows: mask.rows(),
+            cols: mask.cols(),
+            wrap,
+            mask: Some(mask),
+            crossings: Vec::new(),
+            tunnels: Vec::new(),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Whether some cells are left out of the maze by a mask.
+    pub fn is_masked(&self) -> bool {
+        self.mask.is_some()
+    }
+
+    /// Whether any passage can tunnel under a cell.
+    pub fn is_woven(&self) -> bool {
+        !self.crossings.is_empty()
+    }
+
+    /// Whether an edge joins two cells in the same row, so that its passage
+    /// runs east and west, for biasing the direction of passages.
+    pub fn is_east_west(&self, edge_id: usize) -> bool {
+        let mut ends = self.edge_nodes(edge_id);
+        ends.next().unwrap() / self.cols == ends.next().unwrap() / self.cols
+    }
+
+    /// Weaves the grid, so that some passages may tunnel under a cell to
+    /// the cell beyond it. Each cell away from the edge of the grid
+    /// becomes a crossing with the given probability, as long as it does
+    /// not touch another crossing and the cells around it are enabled.
+    /// Laying the maze out then makes sure every open tunnel runs under a
+    /// passage that crosses straight over it.
+    pub fn with_weave(mut self, density: f64, rng: &mut impl rand::Rng) -> Self {
+        self.crossings.clear();
+        self.tunnels = vec![None; self.rows * self.cols];
+        for row in 1..self.rows.saturating_sub(1) {
+            for col in 1..self.cols.saturating_sub(1) {
+                if !rng.random_bool(density) {
+                    continue
+                }
+                let clear = (row - 1..=row + 1).all(|r| (col - 1..=col + 1).all(|c| {
+                    self.tunnels[r * self.cols + c].is_none()
+                        && (self.cell_enabled(r, c) || (r != row && c != col))
+                }));
+                if !clear {
+                    continue
+                }
+                let tunnel = if rng.random_bool(0.5) { Tunnel::EastWest } else { Tunnel::NorthSouth };
+                self.tunnels[row * self.cols + col] = Some((tunnel, self.crossings.len()));
+                self.crossings.push(row * self.cols + col);
+            }
+        }
+        self
+    }
+
+    #[inline]
+    fn tunnel(&self, node_id: usize) -> Option<Tunnel> {
+        self.tunnels.get(node_id).copied().flatten().map(|(tunnel, _k)| tunnel)
+    }
+
+    /// How far apart the two ends of a tunnel are in node ids.
+    #[inline]
+    fn tunnel_step(&self, tunnel: Tunnel) -> usize {
+        match tunnel {
+            Tunnel::EastWest => 1,
+            Tunnel::NorthSouth => self.cols,
+        }
+    }
+
+    /// How many edges there are before the crossing edges.
+    #[inline]
+    fn grid_edges(&self) -> usize {
+        self.rows * self.east_len() + self.south_len() * self.cols
+    }
+
+    /// Whether a grid edge runs along a tunnel into a crossing cell, where
+    /// the crossing edge replaces it.
+    fn edge_tunnelled(&self, edge_id: usize) -> bool {
+        if self.crossings.is_empty() {
+            return false
+        }
+        let (_row, col) = rq!((edge_id) / (self.edge_row_len()));
+        let tunnel = if self.is_edge_horizontal(col) { Tunnel::NorthSouth } else { Tunnel::EastWest };
+        self.edge_nodes(edge_id).any(|nid| self.tunnel(nid) == Some(tunnel))
+    }
+
+    /// The crossing edges that start or end at a node.
+    fn crossing_edges(&self, node_id: usize) -> impl Iterator<Item=usize> + '_ {
+        let candidates = if self.crossings.is_empty() {
+            [None; 4]
+        } else {
+            [
+                Some((node_id + 1, Tunnel::EastWest)),
+                node_id.checked_sub(1).map(|nid| (nid, Tunnel::EastWest)),
+                Some((node_id + self.cols, Tunnel::NorthSouth)),
+                node_id.checked_sub(self.cols).map(|nid| (nid, Tunnel::NorthSouth)),
+            ]
+        };
+        candidates.into_iter()
+            .flatten()
+            .filter_map(move |(nid, tunnel)| match self.tunnels.get(nid) {
+                Some(Some((t, k))) if *t == tunnel => Some(self.grid_edges() + k),
+                _ => None,
+            })
+    }
+
+    #[inline]
+    fn cell_enabled(&self, row: usize, col: usize) -> bool {
+        match &self.mask {
+            None => true,
+            Some(mask) => mask.is_enabled(row, col),
+        }
+    }
+
+    #[inline]
+    fn wraps_cols(&self) -> bool {
+        self.wrap != Wrap::None && self.cols > 2
+    }
+
+    #[inline]
+    fn wraps_rows(&self) -> bool {
+        self.wrap == Wrap::Torus && self.rows > 2
+    }
+
+    /// How many east edges there are in a row.
+    #[inline]
+    fn east_len(&self) -> usize {
+        if self.wraps_cols() {
+            self.cols
+        } else {
+            self.cols - 1
+        }
+    }
+
+    /// Whether an edge leads into or out of a crossing cell, or tunnels
+    /// under one, so that closing it could leave a tunnel under a dead end.
+    fn touches_crossing(&self, edge_id: usize) -> bool {
+        edge_id >= self.grid_edges() || self.edge_nodes(edge_id).any(|nid| self.tunnel(nid).is_some())
+    }
+
+    /// Opens the closed side of a crossing cell that is a dead end, and
+    /// closes a passage elsewhere on the loop that makes, so the maze
+    /// stays a tree. `false` if there is no passage it can close.
+    fn open_corridor(&self, crossing: usize, closed: usize, edges: &mut [Edge]) -> bool {
+        let tree = |direction: EdgeDirection| matches!(direction, EdgeDirection::Forward | EdgeDirection::Backward);
+        let far = follow_edge(self, crossing, closed).unwrap();
+        let Some(path) = search(self, edges, far, crossing, |eid| eid != closed && tree(edges[eid].direction)) else {
+            return false
+        };
+        let Some(wall) = path.into_iter().find(|eid| !self.touches_crossing(*eid)) else {
+            return false
+        };
+        edges[wall].direction = EdgeDirection::Closed;
+        edges[closed].direction = EdgeDirection::Forward;
+        true
+    }
+
+    /// Closes a tunnel and joins the two parts of the maze it held together
+    /// through some other wall instead. `false` if there is no wall it can
+    /// open.
+    fn close_tunnel(&self, tunnel: usize, nodes: &[Node], edges: &mut [Edge]) -> bool {
+        let start = self.edge_nodes(tunnel).next().unwrap();
+        let group = nodes[start].group;
+        let mut side = vec![false; self.num_nodes()];
+        let mut visiting = vec![start];
+        side[start] = true;
+        while let Some(nid) = visiting.pop() {
+            for eid in self.node_edges(nid).filter(|eid| *eid != tunnel && edges[*eid].direction.is_open()) {
+                let other = follow_edge(self, nid, eid).unwrap();
+                if !side[other] {
+                    side[other] = true;
+                    visiting.push(other);
+                }
+            }
+        }
+        if self.edge_nodes(tunnel).all(|nid| side[nid]) {
+            // The tunnel closed a loop, so nothing comes apart.
+            edges[tunnel].direction = EdgeDirection::Closed;
+            return true
+        }
+        let Some(wall) = (0..self.grid_edges())
+            .filter(|eid| edges[*eid].direction == EdgeDirection::Closed && !self.touches_crossing(*eid))
+            .find(|eid| {
+                let mut ends = self.edge_nodes(*eid);
+                let (a, b) = (ends.next().unwrap(), ends.next().unwrap());
+                side[a] != side[b] && nodes[a].group == group && nodes[b].group == group
+            }) else {
+            return false
+        };
+        edges[tunnel].direction = EdgeDirection::Closed;
+        edges[wall].direction = EdgeDirection::Forward;
+        true
+    }
+
+    /// Points every passage of the tree back towards its root again, after
+    /// [`SpaceSquare::open_corridor`] or [`SpaceSquare::close_tunnel`] have
+    /// moved some.
+    fn reorient(&self, nodes: &[Node], edges: &mut [Edge]) {
+        let mut tree = edges.iter()
+            .map(|e| matches!(e.direction, EdgeDirection::Forward | EdgeDirection::Backward))
+            .collect::<Vec<_>>();
+        let mut visiting = (0..self.num_nodes()).filter(|nid| nodes[*nid].root).collect::<Vec<_>>();
+        while let Some(nid) = visiting.pop() {
+            for eid in self.node_edges(nid).collect::<Vec<_>>() {
+                if tree[eid] {
+                    tree[eid] = false;
+                    let child = follow_edge(self, nid, eid).unwrap();
+                    open_edge(&mut edges[eid], nid, child);
+                    visiting.push(child);
+                }
+            }
+        }
+    }
+
+    /// How many rows have south edges.
+    #[inline]
+    fn south_len(&self) -> usize {
+        if self.wraps_rows() {
+            self.rows
+        } else {
+            self.rows - 1
+        }
+    }
+
+    /// The sides of enabled cells that do not lead to another enabled cell.
+    fn mask_outline(&self) -> Vec<([f32; 2], [f32; 2])> {
+        (0..self.rows)
+            .flat_map(|row| (0..self.cols).map(move |col| (row, col)))
+            .filter(|(row, col)| self.cell_enabled(*row, *col))
+            .flat_map(|(row, col)| self.outer_sides(row, col))
+            .collect()
+    }
+
+    /// The sides of a cell that face out of the maze, north, south, west
+    /// then east.
+    fn outer_sides(&self, row: usize, col: usize) -> Vec<([f32; 2], [f32; 2])> {
+        let (rows, cols) = (self.rows, self.cols);
+        let (y, x) = (row as f32, col as f32);
+        let north = (row > 0 || self.wraps_rows())
+            && self.cell_enabled((row + rows - 1) % rows, col);
+        let south = (row + 1 < rows || self.wraps_rows())
+            && self.cell_enabled((row + 1) % rows, col);
+        let west = (col > 0 || self.wraps_cols())
+            && self.cell_enabled(row, (col + cols - 1) % cols);
+        let east = (col + 1 < cols || self.wraps_cols())
+            && self.cell_enabled(row, (col + 1) % cols);
+        let mut sides = Vec::new();
+        if !north {
+            sides.push(([ y - 0.5, x - 0.5 ], [ y - 0.5, x + 0.5 ]));
+        }
+        if !south {
+            sides.push(([ y + 0.5, x - 0.5 ], [ y + 0.5, x + 0.5 ]));
+        }
+        if !west {
+            sides.push(([ y - 0.5, x - 0.5 ], [ y + 0.5, x - 0.5 ]));
+        }
+        if !east {
+            sides.push(([ y - 0.5, x + 0.5 ], [ y + 0.5, x + 0.5 ]));
+        }
+        sides
+    }
+
     #[inline]
     fn edge_row_len(&self) -> usize {
-        self.cols * 2 - 1
+        self.east_len() + self.cols
     }
 
     #[inline]
     fn is_edge_horizontal(&self, col: usize) -> bool {
-        col >= self.cols - 1
+        col >= self.east_len()
     }
 }
 
@@ -204,15 +527,60 @@ impl Space for SpaceSquare {
         self.rows * self.cols
     }
     fn num_edges(&self) -> usize {
-        self.rows * self.edge_row_len() - self.cols
+        self.grid_edges() + self.crossings.len()
     }
     fn node_edges(&self, node_id: usize) -> impl Iterator<Item=usize> {
         let (row, col) = rq!((node_id) / (self.cols));
         SpaceSquareEdgeIterator::new(self, row, col)
+            .filter(move |eid| {
+                self.mask.is_none() || self.edge_nodes(*eid).all(|nid| self.node_enabled(nid))
+            })
+            .filter(move |eid| !self.edge_tunnelled(*eid))
+            .chain(self.crossing_edges(node_id))
     }
     fn edge_nodes(&self, edge_id: usize) -> impl Iterator<Item=usize> {
-        let (row, col) = rq!((edge_id) / (self.edge_row_len()));
-        SpaceSquareNodeIterator::new(self, row, col)
+        let ends = if edge_id >= self.grid_edges() {
+            let crossing = self.crossings[edge_id - self.grid_edges()];
+            let step = self.tunnel_step(self.tunnel(crossing).unwrap());
+            [crossing - step, crossing + step]
+        } else {
+            let (row, col) = rq!((edge_id) / (self.edge_row_len()));
+            let mut ends = SpaceSquareNodeIterator::new(self, row, col);
+            [ends.next().unwrap(), ends.next().unwrap()]
+        };
+        ends.into_iter()
+    }
+    fn node_enabled(&self, node_id: usize) -> bool {
+        let (row, col) = rq!((node_id) / (self.cols));
+        self.cell_enabled(row, col)
+    }
+    fn node_on_border(&self, node_id: usize) -> bool {
+        let (row, col) = rq!((node_id) / (self.cols));
+        self.cell_enabled(row, col) && !self.outer_sides(row, col).is_empty()
+    }
+    /// Makes every open tunnel run under a corridor that crosses over it.
+    /// Where the crossing cell is a dead end instead, its other side is
+    /// opened, or failing that the tunnel is closed again.
+    fn settle(&self, nodes: &[Node], edges: &mut [Edge]) {
+        let mut moved = false;
+        for (k, crossing) in self.crossings.iter().enumerate() {
+            let tunnel = self.grid_edges() + k;
+            if !edges[tunnel].direction.is_open() {
+                continue
+            }
+            let (open, closed): (Vec<_>, Vec<_>) = self.node_edges(*crossing)
+                .partition(|eid| edges[*eid].direction.is_open());
+            let settled = match (open.len(), closed.first()) {
+                (2, _) => continue,
+                (1, Some(closed)) => self.open_corridor(*crossing, *closed, edges)
+                    || self.close_tunnel(tunnel, nodes, edges),
+                _ => self.close_tunnel(tunnel, nodes, edges),
+            };
+            moved |= settled;
+        }
+        if moved {
+            self.reorient(nodes, edges);
+        }
     }
 }
 
@@ -223,18 +591,65 @@ impl SpaceRenderer<2> for SpaceSquare {
         let end = end_nodes.next().unwrap();
         let (start_row, start_col) = rq!((start) / (self.cols));
         let (end_row, end_col) = rq!((end) / (self.cols));
+        // An edge that wraps round ends just past the far side, so its wall
+        // sits on the border.
+        let end_row = if end_row < start_row { self.rows } else { end_row };
+        let end_col = if end_col < start_col { self.cols } else { end_col };
         ([ start_row as f32, start_col as f32 ], [ end_row as f32, end_col as f32 ])
     }
     fn node_position(&self, node_id: usize) -> [f32; 2] {
         let (row, col) = rq!((node_id) / (self.cols));
         [ row as f32, col as f32 ]
     }
+    fn edge_kind(&self, edge_id: usize) -> EdgeKind {
+        if edge_id >= self.grid_edges() {
+            EdgeKind::Under
+        } else {
+            EdgeKind::Adjacent
+        }
+    }
+    fn wrap_position(&self, edge_id: usize) -> Option<([f32; 2], [f32; 2])> {
+        let (start, end) = self.edge_position(edge_id);
+        if end[0] >= self.rows as f32 {
+            let col = start[1];
+            Some(([ -0.5, col - 0.5 ], [ -0.5, col + 0.5 ]))
+        } else if end[1] >= self.cols as f32 {
+            let row = start[0];
+            Some(([ row - 0.5, -0.5 ], [ row + 0.5, -0.5 ]))
+        } else {
+            None
+        }
+    }
+    fn border_opening(&self, node_id: usize) -> Option<([f32; 2], [f32; 2])> {
+        let (row, col) = rq!((node_id) / (self.cols));
+        self.outer_sides(row, col).into_iter().next()
+    }
+    fn border_walls(&self) -> Option<Vec<([f32; 2], [f32; 2])>> {
+        if self.mask.is_some() {
+            return Some(self.mask_outline())
+        }
+        if !self.wraps_cols() && !self.wraps_rows() {
+            return None
+        }
+        let (bottom, right) = (self.rows as f32 - 0.5, self.cols as f32 - 0.5);
+        let mut walls = Vec::new();
+        if !self.wraps_rows() {
+            walls.push(([ -0.5, -0.5 ], [ -0.5, right ]));
+            walls.push(([ bottom, -0.5 ], [ bottom, right ]));
+        }
+        if !self.wraps_cols() {
+            walls.push(([ -0.5, -0.5 ], [ bottom, -0.5 ]));
+            walls.push(([ -0.5, right ], [ bottom, right ]));
+        }
+        Some(walls)
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use crate::space::Space;
+    use crate::edge::EdgeDirection;
     use super::*;
 
 
@@ -323,6 +738,230 @@ mod tests {
         test!(16 => vec![10, 11]);
     }
 
+    #[test]
+    fn test_wrap() {
+        init_tests();
+        let space = SpaceSquare::with_wrap(3, 4, Wrap::Cylinder);
+        assert_eq!(space.num_edges(), 20);
+        let space = SpaceSquare::with_wrap(3, 4, Wrap::Torus);
+        assert_eq!(space.num_edges(), 24);
+        for nid in 0..space.num_nodes() {
+            assert_eq!(space.node_edges(nid).count(), 4, "node {nid}");
+            for eid in space.node_edges(nid) {
+                let nodes = space.edge_nodes(eid).collect::<Vec<_>>();
+                assert_eq!(nodes.len(), 2);
+                assert!(nodes.contains(&nid), "edge {eid} does not touch node {nid}: {nodes:?}");
+            }
+        }
+        let mut edges = space.node_edges(0).collect::<Vec<_>>();
+        edges.sort();
+        assert_eq!(edges, vec![0, 3, 4, 20]);
+        for eid in 0..space.num_edges() {
+            let (start, end) = space.edge_position(eid);
+            assert!((start[0] == end[0]) || (start[1] == end[1]), "edge {eid} is diagonal: {start:?} {end:?}");
+        }
+    }
+
+    #[test]
+    fn test_mask() {
+        init_tests();
+        use rand::SeedableRng;
+        let mask = Mask::from_ascii("\
+            .###.\n\
+            #####\n\
+            ##.##\n\
+            #####\n\
+            .###.\n\
+        ");
+        assert_eq!((mask.rows(), mask.cols()), (5, 5));
+        let space = SpaceSquare::with_mask(mask, Wrap::None);
+        assert!(!space.node_enabled(0));
+        assert!(!space.node_enabled(12));
+        assert_eq!(space.node_edges(0).count(), 0);
+        let mut edges = space.node_edges(7).collect::<Vec<_>>();
+        edges.sort();
+        assert_eq!(edges, vec![6, 10, 11]);
+
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let (nodes, edges) = space.layout(3, &mut rng).unwrap();
+        for (nid, node) in nodes.iter().enumerate() {
+            assert_eq!(node.group.is_some(), space.node_enabled(nid), "node {nid}");
+            assert!(!node.root || space.node_enabled(nid), "node {nid}");
+        }
+        let open = edges.iter()
+            .filter(|e| matches!(e.direction, EdgeDirection::Forward | EdgeDirection::Backward))
+            .count();
+        assert_eq!(open, 20 - 3);
+        for _ in 0..20 {
+            let (start, end) = space.get_endpoints(&mut rng).unwrap();
+            assert!(space.node_enabled(start) && space.node_enabled(end));
+        }
+        assert_eq!(space.border_walls().unwrap().len(), 20 + 4);
+
+        // Too few enabled cells are an error, not an endless search.
+        let sparse = SpaceSquare::with_mask(Mask::from_ascii("...\n.#.\n..."), Wrap::None);
+        assert_eq!(sparse.get_endpoints(&mut rng), None);
+        assert!(crate::space::n_unique_indices(&sparse, &mut rng, 9, 2).is_err());
+        assert_eq!(crate::space::n_unique_indices(&sparse, &mut rng, 9, 1).unwrap().collect::<Vec<_>>(), vec![4]);
+        assert!(sparse.layout(2, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_weave() {
+        init_tests();
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(8, 8).with_weave(0.5, &mut rng);
+        assert!(!space.crossings.is_empty());
+        assert_eq!(space.num_edges(), 8 * 7 * 2 + space.crossings.len());
+        for (k, crossing) in space.crossings.iter().enumerate() {
+            let eid = space.grid_edges() + k;
+            assert_eq!(space.edge_kind(eid), EdgeKind::Under);
+            assert_eq!(space.node_edges(*crossing).count(), 2, "crossing {crossing}");
+            let ends = space.edge_nodes(eid).collect::<Vec<_>>();
+            for end in ends.iter() {
+                assert!(space.node_edges(*end).any(|e| e == eid), "edge {eid} missing from node {end}");
+            }
+            let step = ends[1] - crossing;
+            assert_eq!(crossing - ends[0], step);
+        }
+        let (nodes, edges) = space.layout(1, &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group == Some(0)));
+        let open = edges.iter()
+            .filter(|e| matches!(e.direction, EdgeDirection::Forward | EdgeDirection::Backward))
+            .count();
+        assert_eq!(open, space.num_nodes() - 1);
+
+        // Every open tunnel runs under a corridor, never a dead end.
+        let mut tunnels = 0;
+        for seed in 0..20 {
+            let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(seed);
+            let space = SpaceSquare::new(12, 12).with_weave(0.5, &mut rng);
+            for algorithm in [crate::generator::Algorithm::Frontier, crate::generator::Algorithm::Backtracker] {
+                let (nodes, mut edges) = space.layout_with(1, algorithm, &mut rng).unwrap();
+                assert!(nodes.iter().all(|n| n.group == Some(0)));
+                let tree = edges.iter()
+                    .filter(|e| matches!(e.direction, EdgeDirection::Forward | EdgeDirection::Backward))
+                    .count();
+                assert_eq!(tree, space.num_nodes() - 1, "seed {seed}");
+                for (k, crossing) in space.crossings.iter().enumerate() {
+                    if edges[space.grid_edges() + k].direction.is_open() {
+                        tunnels += 1;
+                        assert!(space.node_edges(*crossing).all(|eid| edges[eid].direction.is_open()), "seed {seed} crossing {crossing}");
+                    }
+                }
+                // Still a tree that solves back to its root.
+                let root = nodes.iter().position(|n| n.root).unwrap();
+                let far = (root + space.num_nodes() / 2) % space.num_nodes();
+                let path = space.solve(&mut edges, root, far).collect::<Vec<_>>();
+                assert_eq!(path.iter().fold(root, |nid, eid| follow_edge(&space, nid, *eid).unwrap()), far, "seed {seed}");
+            }
+        }
+        assert!(tunnels > 100, "{tunnels} tunnels open");
+
+        // A tunnel that is also a gateway keeps both classes.
+        let mut edges = edges;
+        let gateway = space.grid_edges() + (0..space.crossings.len())
+            .find(|k| edges[space.grid_edges() + k].direction.is_open())
+            .unwrap();
+        edges[gateway].direction = EdgeDirection::Border;
+        let svg = crate::render::render_svg_2d(&space, &edges, &nodes, 8, 8, 10.0, 0, 63).to_string();
+        assert!(svg.contains(&format!("id=\"wall_{gateway}\"")));
+        assert_eq!(svg.matches("class=\"tunnel gateway\"").count(), 1);
+    }
+
+    #[test]
+    fn test_backtracker() {
+        init_tests();
+        use rand::SeedableRng;
+        use crate::generator::Algorithm;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(20, 20);
+        let dead_ends = |edges: &[crate::edge::Edge]| (0..space.num_nodes())
+            .filter(|nid| space.node_edges(*nid).filter(|eid| edges[*eid].direction != EdgeDirection::Closed).count() == 1)
+            .count();
+        let (nodes, mut edges) = space.layout_with(3, Algorithm::Backtracker, &mut rng).unwrap();
+        assert!(nodes.iter().all(|n| n.group.is_some()));
+        assert_eq!(nodes.iter().filter(|n| n.root).count(), 3);
+        let open = edges.iter().filter(|e| e.direction != EdgeDirection::Closed).count();
+        assert_eq!(open, space.num_nodes() - 3);
+        let winding = dead_ends(&edges);
+        let (_nodes, bushy) = space.layout_with(3, Algorithm::Frontier, &mut rng).unwrap();
+        assert!(winding < dead_ends(&bushy), "{winding} dead ends");
+        let root = nodes.iter().position(|n| n.root).unwrap();
+        let far = (0..space.num_nodes()).rev().find(|nid| *nid != root && nodes[*nid].group == nodes[root].group).unwrap();
+        assert!(space.solve(&mut edges, root, far).count() > 0);
+    }
+
+    #[test]
+    fn test_braid() {
+        init_tests();
+        use rand::SeedableRng;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(10, 10);
+        let dead_ends = |edges: &[crate::edge::Edge]| (0..space.num_nodes())
+            .filter(|nid| space.node_edges(*nid).filter(|eid| edges[*eid].direction.is_open()).count() == 1)
+            .count();
+        let (nodes, mut edges) = space.layout(1, &mut rng).unwrap();
+        let before = dead_ends(&edges);
+        space.braid(&nodes, &mut edges, 0.5, &mut rng);
+        let after = dead_ends(&edges);
+        assert!(after < before && after > 0, "{before} -> {after}");
+        space.braid(&nodes, &mut edges, 1.0, &mut rng);
+        assert_eq!(dead_ends(&edges), 0);
+        let loops = edges.iter().filter(|e| e.direction == EdgeDirection::Open).count();
+        assert!(loops > 0);
+
+        let path = space.solve_search(&mut edges, 0, 99).unwrap();
+        assert!(path.len() >= 18);
+        assert_eq!(edges.iter().filter(|e| e.solution).count(), path.len());
+        let mut nid = 0;
+        for eid in path {
+            nid = space.edge_nodes(eid).find(|other| *other != nid).unwrap();
+        }
+        assert_eq!(nid, 99);
+        assert_eq!(space.solve_search(&mut edges, 5, 5), Some(vec![]));
+    }
+
+    #[test]
+    fn test_endpoints() {
+        init_tests();
+        use rand::SeedableRng;
+        use crate::space::Endpoints;
+        use crate::render::render_svg_2d_openings;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceSquare::new(8, 8);
+        let (nodes, mut edges) = space.layout(1, &mut rng).unwrap();
+        let distance = |edges: &mut [crate::edge::Edge], a: usize, b: usize| {
+            let path = space.solve_search(edges, a, b).unwrap();
+            edges.iter_mut().for_each(|e| e.solution = false);
+            path.len()
+        };
+        let diameter = (0..64)
+            .flat_map(|a| (0..64).map(move |b| (a, b)))
+            .map(|(a, b)| distance(&mut edges, a, b))
+            .max()
+            .unwrap();
+        let (start, end) = space.choose_endpoints(&nodes, &edges, Endpoints::Farthest, &mut rng).unwrap();
+        assert_eq!(distance(&mut edges, start, end), diameter);
+
+        let (start, end) = space.choose_endpoints(&nodes, &edges, Endpoints::MinDistance(diameter - 2), &mut rng).unwrap();
+        assert!(distance(&mut edges, start, end) >= diameter - 2);
+        assert_eq!(space.choose_endpoints(&nodes, &edges, Endpoints::MinDistance(diameter + 1), &mut rng), None);
+        assert_eq!(space.choose_endpoints(&nodes, &edges, Endpoints::DifferentZones, &mut rng), None);
+
+        let (start, end) = space.choose_endpoints(&nodes, &edges, Endpoints::Border, &mut rng).unwrap();
+        assert!(space.node_on_border(start) && space.node_on_border(end));
+        assert!(!space.node_on_border(9));
+        assert_eq!(space.border_opening(9), None);
+        let svg = render_svg_2d_openings(&space, &edges, &nodes, 8, 8, 10.0, start, end).to_string();
+        assert!(!svg.contains("<rect class=\"wall\""));
+        // Each opening splits a side of the border in two, unless it is at
+        // a corner.
+        let sides = svg.matches("class=\"wall\" d=").count() - edges.iter().filter(|e| e.direction == EdgeDirection::Closed).count();
+        assert!((4..=6).contains(&sides), "{sides} border walls");
+    }
+
     #[test]
     fn test_edge_position() {
         init_tests();
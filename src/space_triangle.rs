@@ -0,0 +1,279 @@
+use std::iter::Iterator;
+use super::space::Space;
+use super::render::SpaceRenderer;
+
+macro_rules! rq {
+    ( ( $lhs:expr ) / ( $rhs:expr )) => {
+        (
+            ($lhs as usize / $rhs as usize),
+            ($lhs as usize % $rhs as usize)
+        )
+    }
+}
+
+const ROW_HEIGHT: f32 = 0.866_025_4; // sqrt(3) / 2
+
+/// A grid of alternating up- and down-pointing triangles, each of which has
+/// up to three neighbours.
+///
+/// The cell in row `r` and column `c` points up when `r + c` is even. Its
+/// left and right neighbours share its slanted sides, and it shares its
+/// flat side with the cell above (pointing down) or below (pointing up).
+#[derive(Debug)]
+pub struct SpaceTriangle {
+    rows: usize,
+    cols: usize,
+}
+
+type Side = (Option<usize>, ([f32; 2], [f32; 2]));
+
+impl SpaceTriangle {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self{
+            rows,
+            cols,
+        }
+    }
+
+    /// The width and height of the grid in cell units, rounded up, for
+    /// sizing a document.
+    pub fn extent(&self) -> (usize, usize) {
+        (
+            (self.cols + 1).div_ceil(2),
+            (self.rows as f32 * ROW_HEIGHT).ceil() as usize,
+        )
+    }
+
+    #[inline]
+    fn is_up(row: usize, col: usize) -> bool {
+        (row + col).is_multiple_of(2)
+    }
+
+    /// How many up-pointing cells in a row have a neighbour below them.
+    #[inline]
+    fn vertical_len(&self, row: usize) -> usize {
+        if row.is_multiple_of(2) {
+            self.cols.div_ceil(2)
+        } else {
+            self.cols / 2
+        }
+    }
+
+    /// Each row holds its own sideways edges followed by the edges down to
+    /// the next row.
+    #[inline]
+    fn edge_row_start(&self, row: usize) -> usize {
+        row * (self.cols - 1)
+            + row.div_ceil(2) * self.vertical_len(0)
+            + (row / 2) * self.vertical_len(1)
+    }
+
+    #[inline]
+    fn down_edge(&self, row: usize, col: usize) -> usize {
+        self.edge_row_start(row) + self.cols - 1 + col / 2
+    }
+
+    /// A corner of the grid, by line of rows and by half-column.
+    #[inline]
+    fn vertex(&self, line: usize, half_col: usize) -> [f32; 2] {
+        [line as f32 * ROW_HEIGHT - 0.5, half_col as f32 * 0.5 - 0.5]
+    }
+
+    fn corners(&self, row: usize, col: usize) -> [[f32; 2]; 3] {
+        if Self::is_up(row, col) {
+            [self.vertex(row, col + 1), self.vertex(row + 1, col + 2), self.vertex(row + 1, col)]
+        } else {
+            [self.vertex(row, col), self.vertex(row, col + 2), self.vertex(row + 1, col + 1)]
+        }
+    }
+
+    /// Each side of a cell with the edge that crosses it, if any.
+    fn sides(&self, node_id: usize) -> [Side; 3] {
+        let (row, col) = rq!((node_id) / (self.cols));
+        let west = (col > 0).then(|| self.edge_row_start(row) + col - 1);
+        let east = (col + 1 < self.cols).then(|| self.edge_row_start(row) + col);
+        let [a, b, c] = self.corners(row, col);
+        if Self::is_up(row, col) {
+            let south = (row + 1 < self.rows).then(|| self.down_edge(row, col));
+            [(east, (a, b)), (south, (b, c)), (west, (c, a))]
+        } else {
+            let north = (row > 0).then(|| self.down_edge(row - 1, col));
+            [(north, (a, b)), (east, (b, c)), (west, (c, a))]
+        }
+    }
+}
+
+impl Space for SpaceTriangle {
+    fn num_nodes(&self) -> usize {
+        self.rows * self.cols
+    }
+    fn num_edges(&self) -> usize {
+        if self.num_nodes() == 0 {
+            return 0
+        }
+        self.edge_row_start(self.rows - 1) + self.cols - 1
+    }
+    fn node_edges(&self, node_id: usize) -> impl Iterator<Item=usize> {
+        self.sides(node_id).into_iter().filter_map(|(eid, _side)| eid)
+    }
+    fn edge_nodes(&self, edge_id: usize) -> impl Iterator<Item=usize> {
+        // Rows come in pairs, which together have a whole row's worth of
+        // edges down to the next row.
+        let (pair, idx) = rq!((edge_id) / (self.cols * 3 - 2));
+        let even_len = self.cols - 1 + self.vertical_len(0);
+        let (row, idx) = if idx < even_len {
+            (pair * 2, idx)
+        } else {
+            (pair * 2 + 1, idx - even_len)
+        };
+        let (a, b) = if idx < self.cols - 1 {
+            ((row, idx), (row, idx + 1))
+        } else {
+            let col = (idx - (self.cols - 1)) * 2 + row % 2;
+            ((row, col), (row + 1, col))
+        };
+        [a, b].into_iter().map(|(row, col)| row * self.cols + col)
+    }
+}
+
+impl SpaceRenderer<2> for SpaceTriangle {
+    fn edge_position(&self, edge_id: usize) -> ([f32; 2], [f32; 2]) {
+        let mut end_nodes = self.edge_nodes(edge_id);
+        let start = end_nodes.next().unwrap();
+        let end = end_nodes.next().unwrap();
+        (self.node_position(start), self.node_position(end))
+    }
+    fn node_position(&self, node_id: usize) -> [f32; 2] {
+        let (row, col) = rq!((node_id) / (self.cols));
+        let [a, b, c] = self.corners(row, col);
+        [(a[0] + b[0] + c[0]) / 3.0, (a[1] + b[1] + c[1]) / 3.0]
+    }
+    fn wall_position(&self, edge_id: usize) -> Option<([f32; 2], [f32; 2])> {
+        let start = self.edge_nodes(edge_id).next().unwrap();
+        self.sides(start).into_iter()
+            .find(|(eid, _side)| *eid == Some(edge_id))
+            .map(|(_eid, side)| side)
+    }
+    fn node_outline(&self, node_id: usize) -> Option<Vec<[f32; 2]>> {
+        let (row, col) = rq!((node_id) / (self.cols));
+        Some(self.corners(row, col).to_vec())
+    }
+    fn border_walls(&self) -> Option<Vec<([f32; 2], [f32; 2])>> {
+        Some(
+            (0..self.num_nodes())
+                .flat_map(|nid| self.sides(nid))
+                .filter(|(eid, _side)| eid.is_none())
+                .map(|(_eid, side)| side)
+                .collect()
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::space::Space;
+    use super::*;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_num_edges() {
+        init_tests();
+        assert_eq!(SpaceTriangle::new(3, 4).num_edges(), 13);
+        assert_eq!(SpaceTriangle::new(3, 5).num_edges(), 17);
+        for (rows, cols) in [(3, 4), (4, 5), (5, 4), (1, 3), (2, 2)] {
+            let space = SpaceTriangle::new(rows, cols);
+            let count = (0..space.num_nodes())
+                .map(|nid| space.node_edges(nid).count())
+                .sum::<usize>();
+            assert_eq!(space.num_edges() * 2, count, "{rows}x{cols}");
+        }
+    }
+
+    #[test]
+    fn test_node_edges() {
+        init_tests();
+        let space = SpaceTriangle::new(3, 4);
+        macro_rules! test {
+            ($idx:literal => $edges:expr) => {
+                {
+                    let mut edges = space.node_edges($idx).collect::<Vec<_>>();
+                    edges.sort();
+                    assert_eq!(edges, $edges);
+                }
+            }
+        }
+        test!(0 => vec![0, 3]);
+        test!(1 => vec![0, 1]);
+        test!(2 => vec![1, 2, 4]);
+        test!(4 => vec![3, 5]);
+        test!(5 => vec![5, 6, 8]);
+        test!(8 => vec![10]);
+        test!(9 => vec![8, 10, 11]);
+    }
+
+    #[test]
+    fn test_edge_nodes() {
+        init_tests();
+        let space = SpaceTriangle::new(5, 6);
+        for nid in 0..space.num_nodes() {
+            for eid in space.node_edges(nid) {
+                let nodes = space.edge_nodes(eid).collect::<Vec<_>>();
+                assert_eq!(nodes.len(), 2);
+                assert!(nodes.contains(&nid), "edge {eid} does not touch node {nid}: {nodes:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_wall_position() {
+        init_tests();
+        let space = SpaceTriangle::new(4, 5);
+        for eid in 0..space.num_edges() {
+            let (a, b) = space.wall_position(eid).unwrap();
+            let length = ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+            assert!((length - 1.0).abs() < 1e-4, "wall {eid} has length {length}");
+        }
+    }
+
+    #[test]
+    fn test_neighbours() {
+        init_tests();
+        let (rows, cols) = (5, 6);
+        let space = SpaceTriangle::new(rows, cols);
+        for nid in 0..space.num_nodes() {
+            let (row, col) = rq!((nid) / (cols));
+            let mut neighbours = space.node_edges(nid)
+                .map(|eid| rq!((space.edge_nodes(eid).find(|other| *other != nid).unwrap()) / (cols)))
+                .collect::<Vec<_>>();
+            neighbours.sort();
+            // Up cells share their flat side with the row below, down cells
+            // with the row above, and nothing wraps round.
+            let flat = if SpaceTriangle::is_up(row, col) {
+                (row + 1 < rows).then_some((row + 1, col))
+            } else {
+                row.checked_sub(1).map(|above| (above, col))
+            };
+            let mut expected = [col.checked_sub(1), (col + 1 < cols).then_some(col + 1)].into_iter()
+                .flatten()
+                .map(|col| (row, col))
+                .chain(flat)
+                .collect::<Vec<_>>();
+            expected.sort();
+            assert_eq!(neighbours, expected, "node {nid}");
+            assert!(neighbours.iter().all(|(r, c)| SpaceTriangle::is_up(*r, *c) != SpaceTriangle::is_up(row, col)));
+        }
+        // Every side of every cell is either crossed by an edge or on the
+        // border.
+        let border = space.border_walls().unwrap().len();
+        assert_eq!(space.num_edges() * 2 + border, space.num_nodes() * 3);
+    }
+}
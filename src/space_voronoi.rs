@@ -0,0 +1,251 @@
+use std::iter::Iterator;
+use std::collections::BTreeMap;
+use super::space::Space;
+use super::space_graph::SpaceGraph;
+use super::render::SpaceRenderer;
+use super::error::*;
+
+/// Sides shorter than this are treated as corners, not shared walls.
+const MIN_SIDE: f32 = 1e-4;
+
+type Segment = ([f32; 2], [f32; 2]);
+
+/// A convex cell, as its corners in order, each with the site across the
+/// side that runs from it to the next corner. `None` is the outer border.
+type Cell = Vec<([f32; 2], Option<usize>)>;
+
+/// Irregular cells made by scattering random sites across a rectangle and
+/// giving each site the area closer to it than to any other site: the
+/// Voronoi diagram of the sites. Cells that share a side are neighbours.
+///
+/// The rectangle runs from `-0.5` to `height - 0.5` and `width - 0.5`, so it
+/// lines up with square cells of the same size.
+#[derive(Debug)]
+pub struct SpaceVoronoi {
+    height: usize,
+    width: usize,
+    sites: Vec<[f32; 2]>,
+    cells: Vec<Cell>,
+    walls: Vec<Segment>,
+    graph: SpaceGraph,
+}
+
+#[inline]
+fn distance_sq(a: [f32; 2], b: [f32; 2]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)
+}
+
+/// Cuts away the part of `cell` closer to `other` than to `site`.
+fn clip(cell: &Cell, site: [f32; 2], other: [f32; 2], other_id: usize) -> Cell {
+    let normal = [other[0] - site[0], other[1] - site[1]];
+    let mid = [(site[0] + other[0]) / 2.0, (site[1] + other[1]) / 2.0];
+    let side = |p: [f32; 2]| (p[0] - mid[0]) * normal[0] + (p[1] - mid[1]) * normal[1];
+    let mut out = Cell::with_capacity(cell.len() + 1);
+    for (k, (corner, label)) in cell.iter().enumerate() {
+        let (next, _) = cell[(k + 1) % cell.len()];
+        let (here, there) = (side(*corner), side(next));
+        let crossing = || {
+            let t = here / (here - there);
+            [corner[0] + t * (next[0] - corner[0]), corner[1] + t * (next[1] - corner[1])]
+        };
+        match (here <= 0.0, there <= 0.0) {
+            (true, true) => out.push((*corner, *label)),
+            (true, false) => {
+                out.push((*corner, *label));
+                out.push((crossing(), Some(other_id)));
+            }
+            (false, true) => out.push((crossing(), *label)),
+            (false, false) => {}
+        }
+    }
+    out
+}
+
+impl SpaceVoronoi {
+    pub fn new(height: usize, width: usize, cells: usize, rng: &mut impl rand::Rng) -> Result<Self> {
+        if height == 0 || width == 0 {
+            return Err(Error::MessageError("the rectangle must have a height and a width"))
+        }
+        let (bottom, right) = (height as f32 - 0.5, width as f32 - 0.5);
+        let sites = (0..cells)
+            .map(|_| [rng.random_range(-0.5..bottom), rng.random_range(-0.5..right)])
+            .collect::<Vec<_>>();
+        let bounds: Cell = vec![
+            ([-0.5, -0.5], None),
+            ([-0.5, right], None),
+            ([bottom, right], None),
+            ([bottom, -0.5], None),
+        ];
+
+        let mut polygons = Vec::<Cell>::with_capacity(cells);
+        for (sid, site) in sites.iter().enumerate() {
+            let mut others = (0..sites.len())
+                .filter(|oid| *oid != sid)
+                .map(|oid| (distance_sq(*site, sites[oid]), oid))
+                .collect::<Vec<_>>();
+            others.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut cell = bounds.clone();
+            for (dist, oid) in others {
+                // Once a site is more than twice as far away as the furthest
+                // corner, its bisector can no longer cut the cell.
+                let reach = cell.iter()
+                    .map(|(corner, _)| distance_sq(*site, *corner))
+                    .fold(0.0f32, f32::max);
+                if dist > 4.0 * reach {
+                    break
+                }
+                if dist <= f32::EPSILON {
+                    continue
+                }
+                cell = clip(&cell, *site, sites[oid], oid);
+            }
+            polygons.push(cell);
+        }
+
+        let mut shared = BTreeMap::<(usize, usize), Segment>::new();
+        for (sid, cell) in polygons.iter().enumerate() {
+            for (k, (corner, label)) in cell.iter().enumerate() {
+                let Some(oid) = label else { continue };
+                let next = cell[(k + 1) % cell.len()].0;
+                if distance_sq(*corner, next) < MIN_SIDE * MIN_SIDE {
+                    continue
+                }
+                shared.entry((sid.min(*oid), sid.max(*oid))).or_insert((*corner, next));
+            }
+        }
+        let (pairs, walls): (Vec<_>, Vec<_>) = shared.into_iter().unzip();
+        let graph = SpaceGraph::new(sites.len(), pairs)?
+            .with_positions(sites.clone())?;
+
+        Ok(Self{
+            height,
+            width,
+            sites,
+            cells: polygons,
+            walls,
+            graph,
+        })
+    }
+
+    /// The width and height of the rectangle in cell units, for sizing a
+    /// document.
+    pub fn extent(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+}
+
+impl Space for SpaceVoronoi {
+    fn num_nodes(&self) -> usize {
+        self.graph.num_nodes()
+    }
+    fn num_edges(&self) -> usize {
+        self.graph.num_edges()
+    }
+    fn node_edges(&self, node_id: usize) -> impl Iterator<Item=usize> {
+        self.graph.node_edges(node_id)
+    }
+    fn edge_nodes(&self, edge_id: usize) -> impl Iterator<Item=usize> {
+        self.graph.edge_nodes(edge_id)
+    }
+}
+
+impl SpaceRenderer<2> for SpaceVoronoi {
+    fn edge_position(&self, edge_id: usize) -> ([f32; 2], [f32; 2]) {
+        self.graph.edge_position(edge_id)
+    }
+    fn node_position(&self, node_id: usize) -> [f32; 2] {
+        self.sites[node_id]
+    }
+    fn wall_position(&self, edge_id: usize) -> Option<([f32; 2], [f32; 2])> {
+        Some(self.walls[edge_id])
+    }
+    fn node_outline(&self, node_id: usize) -> Option<Vec<[f32; 2]>> {
+        Some(self.cells[node_id].iter().map(|(corner, _)| *corner).collect())
+    }
+    fn border_walls(&self) -> Option<Vec<([f32; 2], [f32; 2])>> {
+        Some(
+            self.cells.iter()
+                .flat_map(|cell| {
+                    cell.iter().enumerate()
+                        .filter(|(_k, (_corner, label))| label.is_none())
+                        .map(|(k, (corner, _label))| (*corner, cell[(k + 1) % cell.len()].0))
+                })
+                .collect()
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::space::Space;
+    use super::*;
+    use rand::SeedableRng;
+
+
+    fn init_tests() {
+        log4rs_test_utils::test_logging::init_logging_once_for(
+            None,
+            log::LevelFilter::Debug,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_cells() {
+        init_tests();
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let space = SpaceVoronoi::new(10, 20, 60, &mut rng).unwrap();
+        assert_eq!(space.num_nodes(), 60);
+        let area = (0..space.num_nodes())
+            .map(|nid| {
+                let outline = space.node_outline(nid).unwrap();
+                let twice = (0..outline.len())
+                    .map(|k| {
+                        let (a, b) = (outline[k], outline[(k + 1) % outline.len()]);
+                        a[1] * b[0] - b[1] * a[0]
+                    })
+                    .sum::<f32>();
+                twice.abs() / 2.0
+            })
+            .sum::<f32>();
+        assert!((area - 200.0).abs() < 0.01, "cells cover {area}");
+        for nid in 0..space.num_nodes() {
+            assert!(space.node_edges(nid).count() > 0, "node {nid} has no neighbours");
+        }
+        assert!(SpaceVoronoi::new(0, 20, 60, &mut rng).is_err());
+        assert!(SpaceVoronoi::new(10, 0, 60, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_walls() {
+        init_tests();
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(2);
+        let space = SpaceVoronoi::new(10, 10, 50, &mut rng).unwrap();
+        for eid in 0..space.num_edges() {
+            let ends = space.edge_nodes(eid).collect::<Vec<_>>();
+            let (a, b) = (space.sites[ends[0]], space.sites[ends[1]]);
+            let (from, to) = space.wall_position(eid).unwrap();
+            // The wall lies on the bisector of the two sites, and its middle
+            // is no closer to any other site.
+            for corner in [from, to] {
+                assert!((distance_sq(corner, a).sqrt() - distance_sq(corner, b).sqrt()).abs() < 1e-3, "edge {eid}");
+            }
+            let mid = [(from[0] + to[0]) / 2.0, (from[1] + to[1]) / 2.0];
+            let nearest = space.sites.iter()
+                .map(|site| distance_sq(mid, *site))
+                .fold(f32::MAX, f32::min);
+            assert!(distance_sq(mid, a) - nearest < 1e-3, "edge {eid}");
+            // Each cell has the other across the shared side.
+            for (nid, oid) in [(ends[0], ends[1]), (ends[1], ends[0])] {
+                assert!(space.cells[nid].iter().any(|(_corner, label)| *label == Some(oid)), "edge {eid}");
+            }
+        }
+        // Sides are only on the outer border if they lie on the rectangle.
+        for (from, to) in space.border_walls().unwrap() {
+            let on_side = |p: [f32; 2], q: [f32; 2], k: usize, at: f32| (p[k] - at).abs() < 1e-3 && (q[k] - at).abs() < 1e-3;
+            assert!(on_side(from, to, 0, -0.5) || on_side(from, to, 0, 9.5) || on_side(from, to, 1, -0.5) || on_side(from, to, 1, 9.5),
+                "{from:?} {to:?}");
+        }
+    }
+}